@@ -2,6 +2,8 @@
 const GUI = {
     mode: 'input', // 'input' or 'execution'
     stepMode: false, // ステップ実行モード
+    running: false, // 分割実行中（終わるか中断するまで次の実行を受け付けない）
+    stepsPerFrame: 10000, // 1フレームあたりに実行するステップ数
     
     // 要素の参照
     elements: {
@@ -78,6 +80,13 @@ const GUI = {
             }
         });
         
+        // Escで実行中のプログラムを中断
+        document.addEventListener('keydown', (event) => {
+            if (event.key === 'Escape' && window.ajisaiInterpreter && window.ajisaiInterpreter.is_running()) {
+                window.ajisaiInterpreter.interrupt();
+            }
+        });
+        
         // Memoryエリアのタッチで入力モードに戻る（モバイルのみ）
         this.elements.memoryArea.addEventListener('click', () => {
             if (this.isMobile() && this.mode === 'execution') {
@@ -93,6 +102,7 @@ const GUI = {
     
     // ステップ実行の開始
     async startStepExecution() {
+        if (this.running) return;
         const code = this.elements.codeInput.value.trim();
         if (!code) return;
         
@@ -126,7 +136,7 @@ const GUI = {
     
     // ステップ実行の継続
     async continueStepExecution() {
-        if (!this.stepMode) return;
+        if (!this.stepMode || this.running) return;
         
         try {
             const stepResult = window.ajisaiInterpreter.step();
@@ -241,12 +251,21 @@ const GUI = {
             { name: '-', description: '減算 - 暗黙の反復対応 ( a b -- a-b )' },
             { name: '*', description: '乗算 - 暗黙の反復対応 ( a b -- a*b )' },
             { name: '/', description: '除算 - 暗黙の反復対応 ( a b -- a/b )' },
-            { name: '=', description: '等しい ( a b -- bool )' },
+            { name: '=', description: '等しい、NILとの比較はNIL ( a b -- bool )' },
             { name: '>', description: 'より大きい - 暗黙の反復対応 ( a b -- bool )' },
             { name: '>=', description: '以上 - 暗黙の反復対応 ( a b -- bool )' },
             { name: '<', description: 'より小さい - 暗黙の反復対応 ( a b -- bool )' },
             { name: '<=', description: '以下 - 暗黙の反復対応 ( a b -- bool )' },
             { name: 'NOT', description: '論理否定 - 暗黙の反復対応 ( bool -- bool )' },
+            { name: 'AND', description: '論理積 - 暗黙の反復対応 ( bool bool -- bool )' },
+            { name: 'OR', description: '論理和 - 暗黙の反復対応 ( bool bool -- bool )' },
+            { name: 'XOR', description: '排他的論理和 - 暗黙の反復対応 ( bool bool -- bool )' },
+            { name: 'ALL?', description: 'すべての要素が真か ( vec -- bool )' },
+            { name: 'ANY?', description: 'いずれかの要素が真か ( vec -- bool )' },
+            { name: 'NONE?', description: 'どの要素も真でないか ( vec -- bool )' },
+            { name: 'AND-THEN', description: '真のときだけクォーテーションを実行（偽ならfalse） ( bool vec -- bool )' },
+            { name: 'OR-ELSE', description: '偽のときだけクォーテーションを実行（真ならtrue） ( bool vec -- bool )' },
+            { name: 'BROADCAST-POLICY', description: '長さが異なるときの扱いを設定（ERROR/CYCLE/PAD/TRUNCATE） ( str -- )' },
             { name: 'DUP', description: 'スタックトップを複製 ( a -- a a )' },
             { name: 'DROP', description: 'スタックトップを削除 ( a -- )' },
             { name: 'SWAP', description: '上位2つを交換 ( a b -- b a )' },
@@ -265,17 +284,66 @@ const GUI = {
             { name: 'NTH', description: 'N番目の要素を取得（負数は末尾から） ( n vec -- elem )' },
             { name: 'UNCONS', description: 'ベクトルを先頭要素と残りに分解 ( vec -- elem vec\' )' },
             { name: 'EMPTY?', description: 'ベクトルが空かチェック ( vec -- bool )' },
+            { name: 'NIL?', description: '値がNILかチェック ( a -- bool )' },
+            { name: 'COALESCE', description: '値がNILなら代わりの値を使う ( a b -- a|b )' },
+            { name: 'DEFAULT', description: 'COALESCEの別名 ( a b -- a|b )' },
+            { name: 'COMPACT', description: 'NILの要素を取り除く ( vec -- vec\' )' },
+            { name: 'FILL-NIL', description: 'NILの要素を値で埋める ( vec a -- vec\' )' },
             { name: 'DEF', description: '新しいワードを定義 ( vec str -- )' },
             { name: 'IF', description: '条件分岐 ( bool vec vec -- ... )' },
+            { name: 'COND', description: '最初に真となった条件の本体を実行 ( [ [ cond body ] ... ] -- ... )' },
+            { name: 'CASE', description: '値と等しいキーの本体を実行、なければデフォルト ( a [ [ key body ] ... ] vec -- ... )' },
+            { name: 'LET', description: '値を名前に束縛して本体を実行、LET[ ... ]とも書ける ( ... vec vec -- ... )' },
+            { name: 'CALL', description: 'ベクトルをコードとして実行 ( vec -- ... )' },
+            { name: 'DIP', description: '2番目を退避して実行し、後で戻す ( a vec -- ... a )' },
+            { name: 'KEEP', description: '2番目を残したまま実行し、後で再度積む ( a vec -- ... a )' },
+            { name: 'LIFT', description: '引数にVectorがあれば要素ごとに適用 ( a... vec n -- b )' },
+            { name: 'TIMES', description: 'クォーテーションをN回実行 ( n vec -- ... )' },
+            { name: 'WHILE', description: '条件が真の間、本体を繰り返す ( vec vec -- ... )' },
+            { name: 'UNTIL', description: '条件が真になるまで本体を繰り返す ( vec vec -- ... )' },
+            { name: 'DO-RANGE', description: 'startからend-1までのインデックスを積んで本体を実行 ( start end vec -- ... )' },
+            { name: 'MAP', description: '各要素に適用した結果のベクトル ( vec vec -- vec\' )' },
+            { name: 'FILTER', description: '条件が真の要素だけを残す ( vec vec -- vec\' )' },
+            { name: 'REJECT', description: '条件が真の要素を取り除く ( vec vec -- vec\' )' },
+            { name: 'FOLD', description: '初期値から左畳み込み ( vec init vec -- acc )' },
+            { name: 'REDUCE', description: '先頭要素を初期値として畳み込み ( vec vec -- acc )' },
+            { name: 'EACH', description: '各要素を積んで実行 ( vec vec -- ... )' },
+            { name: 'SCAN', description: '畳み込みの途中経過を集める ( vec init vec -- vec\' )' },
+            { name: 'ZIP-WITH', description: '2つのベクトルの要素ごとに適用 ( vec vec vec -- vec\' )' },
+            { name: 'RANGE', description: 'startからend-1までの遅延シーケンス、endがNILなら無限 ( start end -- seq )' },
+            { name: 'ITERATE', description: '初期値に繰り返し適用した無限シーケンス ( a vec -- seq )' },
+            { name: 'TAKE', description: '先頭からN個の要素 ( seq n -- seq\' )' },
+            { name: 'TAKE-WHILE', description: '条件が真の間の要素、無限のシーケンスはFORCEで計算する ( seq vec -- seq\' )' },
+            { name: 'FORCE', description: 'シーケンスの要素を計算してベクトルにする ( seq -- vec )' },
+            { name: 'THROW', description: '値を例外として投げる ( a -- )' },
+            { name: 'TRY', description: 'エラー時はスタックを戻しエラー値を積んでハンドラを実行 ( vec vec -- ... )' },
+            { name: 'ERROR-MESSAGE', description: 'エラー値のメッセージ ( err -- str )' },
+            { name: 'ERROR-VALUE', description: 'エラー値が保持する値 ( err -- a )' },
             { name: 'DEL', description: 'カスタムワードを削除 ( str -- )' },
+            { name: 'RENAME', description: 'カスタムワードの名前を変更し依存ワードも書き換え ( str str -- )' },
+            { name: 'ELEMENTWISE', description: 'カスタムワードを要素ごとに適用するワードにする ( str n -- )' },
             // 出力ワード
             { name: '.', description: '値を出力してドロップ ( a -- )' },
             { name: 'PRINT', description: '値を出力（ドロップしない） ( a -- a )' },
             { name: 'CR', description: '改行を出力 ( -- )' },
             { name: 'SPACE', description: 'スペースを出力 ( -- )' },
             { name: 'SPACES', description: 'N個のスペースを出力 ( n -- )' },
-            { name: 'EMIT', description: '文字コードを文字として出力 ( n -- )' }
+            { name: 'EMIT', description: '文字コードを文字として出力 ( n -- )' },
+            // 実行統計
+            { name: 'STATS', description: '実行した命令数や最大の深さなどの統計 ( -- vec )' }
         ];
+        // ポリシーを指定した2項演算子（+CYCLE、AND-PADなど）
+        const policies = [
+            ['CYCLE', '短い方を繰り返して'],
+            ['PAD', '足りない要素をNILとして'],
+            ['TRUNCATE', '短い方に切り詰めて']
+        ];
+        for (const op of ['+', '-', '*', '/', '>', '>=', '<', '<=', 'AND', 'OR', 'XOR']) {
+            const separator = /^[A-Z]+$/.test(op) ? '-' : '';
+            for (const [policy, description] of policies) {
+                builtinWords.push({ name: `${op}${separator}${policy}`, description: `${op}を${description}適用 ( a b -- c )` });
+            }
+        }
         this.renderWordButtons(this.elements.builtinWordsDisplay, builtinWords, false);
         
         // カスタムワードは初期状態では空
@@ -338,11 +406,15 @@ const GUI = {
     
     // executeCode関数の修正（カスタムワード情報の取得部分）
     async executeCode() {
+        if (this.running) return;
         const code = this.elements.codeInput.value.trim();
         if (!code) return;
         
-        // ステップモードを終了
-        this.stepMode = false;
+        // ステップモードを終了（途中の継続は破棄する）
+        if (this.stepMode) {
+            this.stepMode = false;
+            window.ajisaiInterpreter.cancel();
+        }
         
         // WASMインタープリタが利用可能か確認
         if (!window.HolonWasm || !window.ajisaiInterpreter) {
@@ -356,8 +428,8 @@ const GUI = {
         }
         
         try {
-            // コードを実行
-            const result = window.ajisaiInterpreter.execute(code);
+            // コードを実行（フレームごとに区切ってGUIを固まらせない）
+            const result = await this.runSliced(code);
             
             if (result.status === 'OK') {
                 // 出力がある場合は表示、なければ'OK'
@@ -412,6 +484,35 @@ const GUI = {
         }
     },
     
+    // requestAnimationFrameごとに一定のステップ数だけ実行を進める
+    async runSliced(code) {
+        const interpreter = window.ajisaiInterpreter;
+        interpreter.start(code);
+        this.setRunning(true);
+        try {
+            let output = '';
+            for (;;) {
+                const result = interpreter.run(this.stepsPerFrame);
+                output += result.output || '';
+                if (result.status === 'Done') {
+                    return { status: 'OK', output };
+                }
+                if (result.status === 'Error') {
+                    throw result.message;
+                }
+                await new Promise(resolve => requestAnimationFrame(resolve));
+            }
+        } finally {
+            this.setRunning(false);
+        }
+    },
+    
+    // 実行中は実行ボタンを無効にする
+    setRunning(running) {
+        this.running = running;
+        document.getElementById('run-btn').disabled = running;
+    },
+    
     // WASMの値をJSの形式に変換
     convertWasmValue(wasmValue) {
         if (!wasmValue || wasmValue === null) return null;
@@ -428,7 +529,9 @@ const GUI = {
             'string': Types.STRING,
             'boolean': Types.BOOLEAN,
             'symbol': Types.SYMBOL,
-            'nil': Types.NIL
+            'nil': Types.NIL,
+            'error': Types.ERROR,
+            'sequence': Types.SEQUENCE
         };
         
         return {
@@ -528,6 +631,10 @@ const GUI = {
             }
         } else if (item.type === Types.NIL) {
             return 'nil';
+        } else if (item.type === Types.ERROR) {
+            return `error(${this.formatValue(this.convertWasmValue(item.value))})`;
+        } else if (item.type === Types.SEQUENCE) {
+            return item.value;
         } else {
             return JSON.stringify(item.value);
         }
@@ -5,7 +5,9 @@ const Types = {
     STRING: 'string',
     SYMBOL: 'symbol',
     VECTOR: 'vector',
-    NIL: 'nil'
+    NIL: 'nil',
+    ERROR: 'error',
+    SEQUENCE: 'sequence'
 };
 
 // 値の作成ヘルパー
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 use crate::interpreter::WordDefinition;
+use crate::symbol::Symbol;
 
-pub fn register_builtins(dictionary: &mut HashMap<String, WordDefinition>) {
+pub fn register_builtins(dictionary: &mut HashMap<Symbol, WordDefinition>) {
     // スタック操作
     register_builtin(dictionary, "DUP", "スタックトップを複製 ( a -- a a )");
     register_builtin(dictionary, "DROP", "スタックトップを削除 ( a -- )");
@@ -28,12 +29,57 @@ pub fn register_builtins(dictionary: &mut HashMap<String, WordDefinition>) {
     register_builtin(dictionary, "UNCONS", "ベクトルを先頭要素と残りに分解 ( vec -- elem vec' )");
     register_builtin(dictionary, "EMPTY?", "ベクトルが空かチェック ( vec -- bool )");
     
+    // 欠損値（NIL）の扱い
+    register_builtin(dictionary, "NIL?", "値がNILかチェック ( a -- bool )");
+    register_builtin(dictionary, "COALESCE", "値がNILなら代わりの値を使う ( a b -- a|b )");
+    register_builtin(dictionary, "DEFAULT", "COALESCEの別名 ( a b -- a|b )");
+    register_builtin(dictionary, "COMPACT", "NILの要素を取り除く ( vec -- vec' )");
+    register_builtin(dictionary, "FILL-NIL", "NILの要素を値で埋める ( vec a -- vec' )");
+    
     // 制御構造
     register_builtin(dictionary, "DEF", "新しいワードを定義 ( vec str -- )");
     register_builtin(dictionary, "IF", "条件分岐 ( bool vec vec -- ... )");
+    register_builtin(dictionary, "COND", "最初に真となった条件の本体を実行 ( [ [ cond body ] ... ] -- ... )");
+    register_builtin(dictionary, "CASE", "値と等しいキーの本体を実行、なければデフォルト ( a [ [ key body ] ... ] vec -- ... )");
+    register_builtin(dictionary, "LET", "値を名前に束縛して本体を実行、LET[ ... ]とも書ける ( ... vec vec -- ... )");
+    register_builtin(dictionary, "CALL", "ベクトルをコードとして実行 ( vec -- ... )");
+    register_builtin(dictionary, "DIP", "2番目を退避して実行し、後で戻す ( a vec -- ... a )");
+    register_builtin(dictionary, "KEEP", "2番目を残したまま実行し、後で再度積む ( a vec -- ... a )");
+    register_builtin(dictionary, "LIFT", "引数にVectorがあれば要素ごとに適用 ( a... vec n -- b )");
+    
+    // ループ（再帰を使わずに反復）
+    register_builtin(dictionary, "TIMES", "クォーテーションをN回実行 ( n vec -- ... )");
+    register_builtin(dictionary, "WHILE", "条件が真の間、本体を繰り返す ( vec vec -- ... )");
+    register_builtin(dictionary, "UNTIL", "条件が真になるまで本体を繰り返す ( vec vec -- ... )");
+    register_builtin(dictionary, "DO-RANGE", "startからend-1までのインデックスを積んで本体を実行 ( start end vec -- ... )");
+    
+    // 高階ワード（要素ごとにクォーテーションを実行）
+    register_builtin(dictionary, "MAP", "各要素に適用した結果のベクトル ( vec vec -- vec' )");
+    register_builtin(dictionary, "FILTER", "条件が真の要素だけを残す ( vec vec -- vec' )");
+    register_builtin(dictionary, "REJECT", "条件が真の要素を取り除く ( vec vec -- vec' )");
+    register_builtin(dictionary, "FOLD", "初期値から左畳み込み ( vec init vec -- acc )");
+    register_builtin(dictionary, "REDUCE", "先頭要素を初期値として畳み込み ( vec vec -- acc )");
+    register_builtin(dictionary, "EACH", "各要素を積んで実行 ( vec vec -- ... )");
+    register_builtin(dictionary, "SCAN", "畳み込みの途中経過を集める ( vec init vec -- vec' )");
+    register_builtin(dictionary, "ZIP-WITH", "2つのベクトルの要素ごとに適用 ( vec vec vec -- vec' )");
+    
+    // 遅延シーケンス（通常のワードに渡すと必要な分だけ要素を計算する）
+    register_builtin(dictionary, "RANGE", "startからend-1までの遅延シーケンス、endがNILなら無限 ( start end -- seq )");
+    register_builtin(dictionary, "ITERATE", "初期値に繰り返し適用した無限シーケンス、ITERATE[ ... ]とも書ける ( a vec -- seq )");
+    register_builtin(dictionary, "TAKE", "先頭からN個の要素 ( seq n -- seq' )");
+    register_builtin(dictionary, "TAKE-WHILE", "条件が真の間の要素、無限のシーケンスはFORCEで計算する ( seq vec -- seq' )");
+    register_builtin(dictionary, "FORCE", "シーケンスの要素を計算してベクトルにする ( seq -- vec )");
+    
+    // 例外処理
+    register_builtin(dictionary, "THROW", "値を例外として投げる ( a -- )");
+    register_builtin(dictionary, "TRY", "エラー時はスタックを戻しエラー値を積んでハンドラを実行 ( vec vec -- ... )");
+    register_builtin(dictionary, "ERROR-MESSAGE", "エラー値のメッセージ ( err -- str )");
+    register_builtin(dictionary, "ERROR-VALUE", "エラー値が保持する値 ( err -- a )");
     
     // 辞書操作
     register_builtin(dictionary, "DEL", "カスタムワードを削除 ( str -- )");
+    register_builtin(dictionary, "RENAME", "カスタムワードの名前を変更し依存ワードも書き換え ( str str -- )");
+    register_builtin(dictionary, "ELEMENTWISE", "カスタムワードを要素ごとに適用するワードにする ( str n -- )");
     
     // 算術演算子（暗黙の反復対応）
     register_builtin(dictionary, "+", "加算 - 暗黙の反復対応 ( a b -- a+b )");
@@ -44,12 +90,38 @@ pub fn register_builtins(dictionary: &mut HashMap<String, WordDefinition>) {
     // 比較演算子（暗黙の反復対応）
     register_builtin(dictionary, ">", "より大きい - 暗黙の反復対応 ( a b -- bool )");
     register_builtin(dictionary, ">=", "以上 - 暗黙の反復対応 ( a b -- bool )");
-    register_builtin(dictionary, "=", "等しい ( a b -- bool )");
+    register_builtin(dictionary, "=", "等しい、NILとの比較はNIL ( a b -- bool )");
     register_builtin(dictionary, "<", "より小さい - 暗黙の反復対応 ( a b -- bool )");
     register_builtin(dictionary, "<=", "以下 - 暗黙の反復対応 ( a b -- bool )");
     
     // 論理演算子（暗黙の反復対応）
     register_builtin(dictionary, "NOT", "論理否定 - 暗黙の反復対応 ( bool -- bool )");
+    register_builtin(dictionary, "AND", "論理積 - 暗黙の反復対応 ( bool bool -- bool )");
+    register_builtin(dictionary, "OR", "論理和 - 暗黙の反復対応 ( bool bool -- bool )");
+    register_builtin(dictionary, "XOR", "排他的論理和 - 暗黙の反復対応 ( bool bool -- bool )");
+    register_builtin(dictionary, "ALL?", "すべての要素が真か ( vec -- bool )");
+    register_builtin(dictionary, "ANY?", "いずれかの要素が真か ( vec -- bool )");
+    register_builtin(dictionary, "NONE?", "どの要素も真でないか ( vec -- bool )");
+    register_builtin(dictionary, "AND-THEN", "真のときだけクォーテーションを実行（偽ならfalse） ( bool vec -- bool )");
+    register_builtin(dictionary, "OR-ELSE", "偽のときだけクォーテーションを実行（真ならtrue） ( bool vec -- bool )");
+    
+    // 長さの異なるVector同士のブロードキャスト
+    register_builtin(dictionary, "BROADCAST-POLICY", "長さが異なるときの扱いを設定（ERROR/CYCLE/PAD/TRUNCATE） ( str -- )");
+    for op in ["+", "-", "*", "/", ">", ">=", "<", "<=", "AND", "OR", "XOR"] {
+        // 英字の演算子はハイフンで区切る（AND-CYCLEなど）
+        let separator = if op.chars().all(|c| c.is_ascii_alphabetic()) { "-" } else { "" };
+        for (policy, description) in [
+            ("CYCLE", "短い方を繰り返して"),
+            ("PAD", "足りない要素をNILとして"),
+            ("TRUNCATE", "短い方に切り詰めて"),
+        ] {
+            register_builtin(
+                dictionary,
+                &format!("{}{}{}", op, separator, policy),
+                &format!("{}を{}適用 ( a b -- c )", op, description),
+            );
+        }
+    }
 
     // 出力
     register_builtin(dictionary, ".", "値を出力してドロップ ( a -- )");
@@ -58,11 +130,16 @@ pub fn register_builtins(dictionary: &mut HashMap<String, WordDefinition>) {
     register_builtin(dictionary, "SPACE", "スペースを出力 ( -- )");
     register_builtin(dictionary, "SPACES", "N個のスペースを出力 ( n -- )");
     register_builtin(dictionary, "EMIT", "文字コードを文字として出力 ( n -- )");
+
+    // 実行統計
+    register_builtin(dictionary, "STATS", "実行した命令数や最大の深さなどの統計 ( -- vec )");
 }
 
-fn register_builtin(dictionary: &mut HashMap<String, WordDefinition>, name: &str, description: &str) {
-    dictionary.insert(name.to_string(), WordDefinition {
+fn register_builtin(dictionary: &mut HashMap<Symbol, WordDefinition>, name: &str, description: &str) {
+    dictionary.insert(Symbol::intern(name), WordDefinition {
         tokens: vec![],
+        code: Default::default(),
+        elementwise: None,
         is_builtin: true,
         description: Some(description.to_string()),
     });
@@ -1,26 +1,357 @@
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
 use crate::types::*;
 use crate::tokenizer::*;
 use crate::builtins;
+use crate::symbol::Symbol;
+use crate::stats::{self, ExecutionStats};
+use crate::memory::{self, MemoryReport};
+use crate::trace::{self, LogLevel};
 
 pub struct Interpreter {
     stack: Stack,
     register: Register,
-    dictionary: HashMap<String, WordDefinition>,
-    dependencies: HashMap<String, HashSet<String>>, // word -> それを使用しているワードのセット
-    // ステップ実行用の状態
-    step_tokens: Vec<Token>,
-    step_position: usize,
-    step_mode: bool,
+    dictionary: HashMap<Symbol, WordDefinition>,
+    dependencies: HashMap<Symbol, HashSet<Symbol>>, // word -> それを使用しているワードのセット
+    // 既存のワードを書き換えるたびに増やす（リテラルのコンパイル結果が古いかの判定用）
+    dictionary_version: u64,
+    // リテラルのコンパイル結果（要素の領域のアドレスから引く）
+    compiled_literals: RefCell<HashMap<*const Vec<Value>, CompiledLiteral>>,
+    // 実行中の継続（中断しても次のrunで続きから再開できる）
+    frames: Vec<Frame>,
+    // 継続の中のカスタムワードのフレームの数（実行統計の呼び出しの深さ）
+    call_depth: usize,
+    pending_snapshot: Option<Snapshot>,
     // 出力バッファ
     output_buffer: String,
+    // THROWされた値（TRYで捕捉されるまで保持）
+    thrown: Option<Value>,
+    // 実行制限（呼び出しの深さと実行命令数）
+    max_call_depth: usize,
+    max_steps: Option<u64>,
+    // 今回の実行に適用する実行命令数の上限
+    step_limit: Option<u64>,
+    steps: u64,
+    // 外部から実行を中断するためのフラグ（命令の合間に確認する）
+    interrupt_requested: Cell<bool>,
+    // エラー時に実行前の状態へ戻すかどうか
+    atomic_mode: bool,
+    // 演算子の型が合わないときにエラーにするか（falseなら値をそのまま残す）
+    strict_mode: bool,
+    // 長さの異なるVector同士の演算の扱い
+    broadcast_policy: BroadcastPolicy,
+    // 実行統計（組み込みワードごとの計測はプロファイル中のみ）
+    stats: ExecutionStats,
+    vector_elements_base: u64,
+    profiling: bool,
+}
+
+// フレームはヒープ上に積むため、ネイティブのスタックを消費しない
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10000;
+
+// 完了まで戻らないexecuteで、上限が未設定のときに使う実行命令数の上限
+// （少しずつ進めるrunは中断できるので無制限のまま）
+pub const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+
+// runの結果（エラーはErrで返す）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    Suspended,
+    Done,
+}
+
+// 長さの異なるVector同士に2項演算子を適用するときの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastPolicy {
+    // エラーにする
+    Error,
+    // 短い方を繰り返す（APL/R風）
+    Cycle,
+    // 短い方の足りない要素をNILとする
+    Pad,
+    // 短い方の長さに切り詰める
+    Truncate,
+}
+
+impl BroadcastPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "ERROR" => Some(BroadcastPolicy::Error),
+            "CYCLE" => Some(BroadcastPolicy::Cycle),
+            "PAD" => Some(BroadcastPolicy::Pad),
+            "TRUNCATE" => Some(BroadcastPolicy::Truncate),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BroadcastPolicy::Error => "ERROR",
+            BroadcastPolicy::Cycle => "CYCLE",
+            BroadcastPolicy::Pad => "PAD",
+            BroadcastPolicy::Truncate => "TRUNCATE",
+        }
+    }
+
+    // 要素ごとに適用するVectorの長さから結果の長さを決める
+    fn result_len(self, lens: &[usize]) -> Result<usize, String> {
+        let min = lens.iter().copied().min().unwrap_or(0);
+        let max = lens.iter().copied().max().unwrap_or(0);
+        match self {
+            _ if min == max => Ok(max),
+            BroadcastPolicy::Error => Err("Vector length mismatch".to_string()),
+            BroadcastPolicy::Cycle if min == 0 => Ok(0),
+            BroadcastPolicy::Cycle | BroadcastPolicy::Pad => Ok(max),
+            BroadcastPolicy::Truncate => Ok(min),
+        }
+    }
+
+    // 結果のi番目に対応する要素（足りなければNone）
+    fn element(self, items: &[Value], i: usize) -> Option<&Value> {
+        match self {
+            BroadcastPolicy::Cycle => items.get(i % items.len()),
+            _ => items.get(i),
+        }
+    }
+}
+
+// 実行用にコンパイル済みのクォーテーション（反復のたびに複製しないよう共有する）
+pub(crate) type Quotation = Rc<Vec<Instruction>>;
+
+// 組み込みワードの実装（コンパイル時に名前から解決する）
+type BuiltinFn = fn(&mut Interpreter) -> Result<(), String>;
+
+// コンパイル済みの命令
+#[derive(Clone)]
+pub(crate) enum Instruction {
+    // 値を積む（ベクタのリテラルは構築済み）
+    Push(Value),
+    // 組み込みワード（取り出すarity個の引数のうち有限のシーケンスを先にベクタにする）
+    Builtin { name: Symbol, op: BuiltinFn, arity: usize },
+    // ポリシーを指定した2項演算子（+CYCLEなど）
+    BuiltinWithPolicy { name: Symbol, op: BuiltinFn, policy: BroadcastPolicy },
+    // 直前の説明とともにワードを定義する
+    Def,
+    // 次のDEFへ引き継ぐ説明
+    Describe(String),
+    // カスタムワードの呼び出し（定義時に解決した本体、Noneなら実行時に辞書から引く）
+    Call { name: Symbol, code: Option<Quotation> },
+    // 定義中のワード自身の呼び出し（本体への弱参照、なくなっていれば辞書から引く）
+    Recurse { name: Symbol, code: Weak<Vec<Instruction>> },
+    // 要素ごとのワードの本体（引数にVectorがあれば要素ごとに適用する）
+    Lift { arity: usize, code: Quotation, word: Symbol },
+}
+
+// コンパイル中のワード（自身への再帰呼び出しは、できあがる本体への弱参照で解決する）
+#[derive(Clone)]
+pub(crate) struct Definition {
+    name: Symbol,
+    code: Weak<Vec<Instruction>>,
+}
+
+// ベクタのリテラルをコードとして実行するためのコンパイル結果（要素の領域ごとに保持する）
+// （ワードの定義の中のリテラルは参照を解決しているので、辞書が書き換わったらコンパイルし直す）
+pub(crate) struct CompiledLiteral {
+    // 弱参照が残っている間は領域が再利用されないので、アドレスをキーにできる
+    items: Weak<Vec<Value>>,
+    definition: Option<Definition>,
+    version: u64,
+    code: Quotation,
+}
+
+// 継続を構成するフレーム
+enum Frame {
+    // 命令列の実行位置（wordは実行中のカスタムワード名）
+    Code {
+        code: Quotation,
+        position: usize,
+        word: Option<Symbol>,
+        description: Option<String>,
+        // 末尾呼び出しで置き換えた呼び出し元のワード
+        tail_calls: Option<Box<TailCalls>>,
+    },
+    // クォーテーションの実行が終わるたびに再開する制御構造
+    Control(Control),
+    // TRYの本体を実行中（エラーはここまで巻き戻してハンドラを実行）
+    Try {
+        saved_stack: Stack,
+        handler: Quotation,
+    },
+}
+
+// 末尾呼び出しで消えたフレームのワード（呼び出し履歴の表示用、古い順）
+#[derive(Default)]
+struct TailCalls {
+    // 連続する同じワードは回数でまとめる
+    words: Vec<(Symbol, usize)>,
+    // 記録しきれなかった呼び出しの数
+    elided: usize,
+}
+
+// 無限の末尾再帰でも記録が増え続けないようにする
+const MAX_TAIL_CALL_RECORDS: usize = 16;
+
+impl TailCalls {
+    fn record(&mut self, word: Symbol) {
+        if self.elided == 0 {
+            if let Some((last, count)) = self.words.last_mut() {
+                if *last == word {
+                    *count += 1;
+                    return;
+                }
+            }
+            if self.words.len() < MAX_TAIL_CALL_RECORDS {
+                self.words.push((word, 1));
+                return;
+            }
+        }
+        self.elided += 1;
+    }
+}
+
+enum Control {
+    // 本体の実行後に値を積む（DIP・KEEP）
+    Push(Value),
+    // 本体を順に実行する（値があれば先に積む）
+    Sequence {
+        steps: Vec<(Option<Value>, Quotation)>,
+        index: usize,
+    },
+    Times {
+        body: Quotation,
+        remaining: i64,
+    },
+    While {
+        cond: Quotation,
+        body: Quotation,
+        stage: LoopStage,
+    },
+    Until {
+        body: Quotation,
+        cond: Quotation,
+        stage: LoopStage,
+    },
+    DoRange {
+        body: Quotation,
+        next: i64,
+        end: i64,
+    },
+    Iterate(Iteration),
+    Force(Forcing),
+    Lift(Lifting),
+    Cond {
+        pairs: Vec<(Quotation, Quotation)>,
+        index: usize,
+        conditions: Vec<Value>,
+    },
+}
+
+// WHILE・UNTILで次に行うこと
+#[derive(Clone, Copy)]
+enum LoopStage {
+    Body,
+    Test,
+    Check,
+}
+
+// 要素ごとにクォーテーションを適用する高階ワードの途中状態
+struct Iteration {
+    kind: IterationKind,
+    body: Quotation,
+    items: Vec<Value>,
+    others: Vec<Value>, // ZIP-WITHの2番目のベクトル
+    index: usize,
+    results: Vec<Value>,
+    base: usize, // 適用前のスタックの深さ
+}
+
+// 要素ごとのワード（LIFT）を要素の組ごとに適用する途中状態
+struct Lifting {
+    code: Quotation,
+    word: Option<Symbol>, // 要素ごとのワード（LIFTならNone）
+    calls: Vec<Vec<Value>>, // 適用する引数の組（適用する順）
+    index: usize,
+    results: Vec<Value>,
+    shape: LiftShape,
+    base: usize, // 適用前のスタックの深さ
+}
+
+// 要素ごとの適用結果を組み立てる形（Applyには適用した順に結果を入れる）
+enum LiftShape {
+    Apply,
+    Nil, // 短い方に対応する要素がない位置
+    Vector(Vec<LiftShape>),
+}
+
+// 遅延シーケンスの要素を1ステップに1つずつ計算する途中状態
+// （ITERATE・TAKE-WHILEのクォーテーションも継続に積んで実行する）
+struct Forcing {
+    source: SequenceSource,
+    limit: Option<usize>,
+    step: Option<Quotation>,     // ITERATEのクォーテーション
+    conditions: Vec<Quotation>,  // TAKE-WHILEの述語
+    items: Vec<Value>,
+    // 述語を確かめている途中の要素
+    candidate: Option<Value>,
+    stage: ForceStage,
+    base: usize, // 計算を始める前のスタックの深さ
+    slot: Option<usize>, // 結果で置き換えるスタックの位置（Noneなら積む）
+}
+
+// 遅延シーケンスの計算で次に行うこと
+#[derive(Clone, Copy)]
+enum ForceStage {
+    Next,
+    Step,
+    Condition(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IterationKind {
+    Map,
+    Filter,
+    Reject,
+    Fold,
+    Reduce,
+    Each,
+    Scan,
+    ZipWith,
+}
+
+impl IterationKind {
+    fn word(self) -> &'static str {
+        match self {
+            IterationKind::Map => "MAP",
+            IterationKind::Filter => "FILTER",
+            IterationKind::Reject => "REJECT",
+            IterationKind::Fold => "FOLD",
+            IterationKind::Reduce => "REDUCE",
+            IterationKind::Each => "EACH",
+            IterationKind::Scan => "SCAN",
+            IterationKind::ZipWith => "ZIP-WITH",
+        }
+    }
+}
+
+// アトミック実行のために保存する状態
+struct Snapshot {
+    stack: Stack,
+    register: Register,
+    dictionary: HashMap<Symbol, WordDefinition>,
+    dependencies: HashMap<Symbol, HashSet<Symbol>>,
+    broadcast_policy: BroadcastPolicy,
 }
 
 #[derive(Clone)]
 pub struct WordDefinition {
     pub tokens: Vec<Token>,
+    // DEFの時点でコンパイルした本体
+    pub(crate) code: Quotation,
     pub is_builtin: bool,
     pub description: Option<String>,
+    // ELEMENTWISEで指定した引数の数
+    pub elementwise: Option<usize>,
 }
 
 impl Interpreter {
@@ -30,151 +361,502 @@ impl Interpreter {
             register: None,
             dictionary: HashMap::new(),
             dependencies: HashMap::new(),
-            step_tokens: Vec::new(),
-            step_position: 0,
-            step_mode: false,
+            dictionary_version: 0,
+            compiled_literals: RefCell::new(HashMap::new()),
+            frames: Vec::new(),
+            call_depth: 0,
+            pending_snapshot: None,
             output_buffer: String::new(),
+            thrown: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            step_limit: None,
+            steps: 0,
+            interrupt_requested: Cell::new(false),
+            atomic_mode: false,
+            strict_mode: true,
+            broadcast_policy: BroadcastPolicy::Error,
+            stats: ExecutionStats::default(),
+            vector_elements_base: stats::vector_elements_allocated(),
+            profiling: false,
         };
-        
+
         builtins::register_builtins(&mut interpreter.dictionary);
-        
+
         interpreter
     }
-    
+
     pub fn execute(&mut self, code: &str) -> Result<(), String> {
+        self.start(code)?;
+        self.step_limit = Some(self.max_steps.unwrap_or(DEFAULT_MAX_STEPS));
+        self.resume(None)?;
+        Ok(())
+    }
+
+    // 実行を開始して継続を用意する（実行中ならエラー、先にinterruptかcancelで終わらせる）
+    pub fn start(&mut self, code: &str) -> Result<(), String> {
+        if self.is_running() {
+            return Err("A program is already running (interrupt or cancel it first)".to_string());
+        }
         let tokens = tokenize(code)?;
-        self.execute_tokens_with_context(&tokens)?;
+        // 要素がなくなったリテラルのコンパイル結果を捨てる
+        self.compiled_literals.get_mut().retain(|_, literal| literal.items.strong_count() > 0);
+        let compiled = self.compile(&tokens, None)?;
+        self.thrown = None;
+        self.reset_execution_counters();
+        self.step_limit = self.max_steps;
+        self.interrupt_requested.set(false);
+        self.pending_snapshot = if self.atomic_mode { Some(self.snapshot()) } else { None };
+        self.call_depth = 0;
+        self.frames = vec![Frame::Code {
+            code: Rc::new(compiled),
+            position: 0,
+            word: None,
+            description: None,
+            tail_calls: None,
+        }];
         Ok(())
     }
 
+    // 最大budgetステップだけ実行を進める（ブラウザから少しずつ呼び出す）
+    pub fn run(&mut self, budget: u64) -> Result<RunStatus, String> {
+        self.resume(Some(budget))
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    // 実行中のプログラムを次の命令の手前で中断させる
+    pub fn interrupt(&self) {
+        self.interrupt_requested.set(true);
+    }
+
+    // 実行中の継続を破棄する（アトミックモードなら開始前の状態に戻す）
+    pub fn cancel(&mut self) {
+        self.clear_frames();
+        self.thrown = None;
+        if let Some(snapshot) = self.pending_snapshot.take() {
+            self.restore(snapshot);
+        }
+    }
+
+    fn resume(&mut self, budget: Option<u64>) -> Result<RunStatus, String> {
+        let result = self.run_frames(budget);
+        match &result {
+            Ok(RunStatus::Suspended) => {},
+            Ok(RunStatus::Done) => self.pending_snapshot = None,
+            Err(_) => {
+                if let Some(snapshot) = self.pending_snapshot.take() {
+                    self.restore(snapshot);
+                }
+            },
+        }
+        result
+    }
+
+    // アトミックモードではエラー時にスタック・レジスタ・辞書・ブロードキャストのポリシーを実行前に戻す
+    pub fn set_atomic_mode(&mut self, enabled: bool) {
+        self.atomic_mode = enabled;
+    }
+
+    pub fn is_atomic_mode(&self) -> bool {
+        self.atomic_mode
+    }
+
+    // 厳格モードでは演算子の型の不一致をエラーにする（緩いモードは互換性のため）
+    pub fn set_strict_mode(&mut self, enabled: bool) {
+        self.strict_mode = enabled;
+    }
+
+    pub fn is_strict_mode(&self) -> bool {
+        self.strict_mode
+    }
+
+    pub fn set_broadcast_policy(&mut self, policy: BroadcastPolicy) {
+        self.broadcast_policy = policy;
+    }
+
+    pub fn get_broadcast_policy(&self) -> BroadcastPolicy {
+        self.broadcast_policy
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            stack: self.stack.clone(),
+            register: self.register.clone(),
+            dictionary: self.dictionary.clone(),
+            dependencies: self.dependencies.clone(),
+            broadcast_policy: self.broadcast_policy,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.stack = snapshot.stack;
+        self.register = snapshot.register;
+        self.dictionary = snapshot.dictionary;
+        self.dictionary_version += 1;
+        self.dependencies = snapshot.dependencies;
+        self.broadcast_policy = snapshot.broadcast_policy;
+    }
+
     // 出力バッファを取得してクリア
     pub fn get_output(&mut self) -> String {
         let output = self.output_buffer.clone();
         self.output_buffer.clear();
         output
     }
-    
+
     // 出力バッファに追加
     fn append_output(&mut self, text: &str) {
         self.output_buffer.push_str(text);
     }
 
+    // 実行制限の設定（実行命令数がNoneなら、executeは既定の上限・runは無制限）
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    pub fn set_max_steps(&mut self, steps: Option<u64>) {
+        self.max_steps = steps;
+    }
+
+    fn reset_execution_counters(&mut self) {
+        self.steps = 0;
+        self.stats = ExecutionStats::default();
+        self.vector_elements_base = stats::vector_elements_allocated();
+    }
+
+    // 直近に開始したプログラムの実行統計
+    pub fn get_stats(&self) -> ExecutionStats {
+        let mut stats = self.stats.clone();
+        stats.vector_elements = stats::vector_elements_allocated() - self.vector_elements_base;
+        stats
+    }
+
+    // 組み込みワードごとの呼び出し回数と所要時間を計測するか
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiling
+    }
+
+    // 組み込みワードを実行する（プロファイル中は回数と時間を記録）
+    fn call_builtin(&mut self, name: Symbol, op: BuiltinFn) -> Result<(), String> {
+        if !self.profiling {
+            return op(self);
+        }
+        let started = stats::now_ms();
+        let result = op(self);
+        let entry = self.stats.builtins.entry(name).or_default();
+        entry.calls += 1;
+        entry.time_ms += stats::now_ms() - started;
+        result
+    }
+
+    // 実行命令数を1つ消費し、上限を超えたらエラー
+    fn consume_step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        match self.step_limit {
+            Some(max) if self.steps > max => {
+                Err(self.limit_error(&format!("Step limit ({}) exceeded", max)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // 制限超過のエラーメッセージ（実行中のワードと呼び出し履歴を含む）
+    fn limit_error(&self, message: &str) -> String {
+        let call_chain = self.call_chain();
+        match call_chain.last() {
+            Some((Some(word), _)) => format!("{} in word '{}'. Call chain: {}", message, word, Self::format_call_chain(&call_chain)),
+            _ => format!("{} at top level", message),
+        }
+    }
+
+    // 実行中のカスタムワードの呼び出し履歴と回数（末尾呼び出しで消えたワードも含む）
+    // （ワードがNoneの要素は記録しきれなかった末尾呼び出し）
+    fn call_chain(&self) -> Vec<(Option<&str>, usize)> {
+        let mut call_chain = Vec::new();
+        for frame in &self.frames {
+            match frame {
+                Frame::Code { word: Some(word), tail_calls, .. } => {
+                    if let Some(tail_calls) = tail_calls {
+                        call_chain.extend(tail_calls.words.iter().map(|(word, count)| (Some(word.as_str()), *count)));
+                        if tail_calls.elided > 0 {
+                            call_chain.push((None, tail_calls.elided));
+                        }
+                    }
+                    call_chain.push((Some(word.as_str()), 1));
+                },
+                // 要素ごとに適用している途中のワード
+                Frame::Control(Control::Lift(Lifting { word: Some(word), .. })) => call_chain.push((Some(word.as_str()), 1)),
+                _ => {},
+            }
+        }
+        call_chain
+    }
+
+    // 連続する同じワードは回数でまとめて表示
+    fn format_call_chain(call_chain: &[(Option<&str>, usize)]) -> String {
+        let mut merged: Vec<(Option<&str>, usize)> = Vec::new();
+        for &(name, count) in call_chain {
+            match merged.last_mut() {
+                Some((last, total)) if name.is_some() && *last == name => *total += count,
+                _ => merged.push((name, count)),
+            }
+        }
+        merged.iter()
+            .map(|(name, count)| match name {
+                Some(name) if *count > 1 => format!("{} (x{})", name, count),
+                Some(name) => name.to_string(),
+                None => format!("... ({} more tail calls)", count),
+            })
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
     // ステップ実行の初期化
     pub fn init_step_execution(&mut self, code: &str) -> Result<(), String> {
-        self.step_tokens = tokenize(code)?;
-        self.step_position = 0;
-        self.step_mode = true;
-        Ok(())
+        self.start(code)
     }
 
-    // 1ステップ実行
+    // 1ステップ実行（ワードの内部にも入って1命令ずつ進める）
     pub fn execute_step(&mut self) -> Result<bool, String> {
-        if !self.step_mode || self.step_position >= self.step_tokens.len() {
-            self.step_mode = false;
-            return Ok(false); // 実行完了
-        }
+        Ok(self.resume(Some(1))? == RunStatus::Suspended)
+    }
 
-        let token = self.step_tokens[self.step_position].clone();
-        self.step_position += 1;
+    // ステップ実行の状態を取得（トップレベルのコードの位置）
+    pub fn get_step_info(&self) -> Option<(usize, usize)> {
+        match self.frames.first() {
+            Some(Frame::Code { code, position, .. }) => Some(((*position).min(code.len()), code.len())),
+            _ => None,
+        }
+    }
 
-        // トークンを1つ実行
-        match self.execute_single_token(&token) {
-            Ok(_) => Ok(self.step_position < self.step_tokens.len()),
-            Err(e) => {
-                self.step_mode = false;
-                Err(e)
+    // 継続を最大budgetステップ分進める（Noneなら完了まで）
+    fn run_frames(&mut self, budget: Option<u64>) -> Result<RunStatus, String> {
+        let mut executed = 0;
+        loop {
+            self.drop_finished_frames();
+            if self.frames.is_empty() {
+                return Ok(RunStatus::Done);
+            }
+            // 中断はTRYで捕捉させず、継続を破棄して終了する
+            // （フラグは次のstartまで残す）
+            if self.interrupt_requested.get() {
+                let message = self.limit_error("Interrupted");
+                self.clear_frames();
+                self.thrown = None;
+                return Err(message);
+            }
+            if budget.is_some_and(|budget| executed >= budget) {
+                return Ok(RunStatus::Suspended);
+            }
+            executed += 1;
+            if let Err(message) = self.step_frame() {
+                if let Err(message) = self.unwind(message) {
+                    self.clear_frames();
+                    return Err(message);
+                }
             }
         }
     }
 
-    // ステップ実行の状態を取得
-    pub fn get_step_info(&self) -> Option<(usize, usize)> {
-        if self.step_mode {
-            Some((self.step_position, self.step_tokens.len()))
-        } else {
-            None
+    // 実行し終えたコードフレームと、エラーなく本体を終えたTRYを取り除く
+    fn drop_finished_frames(&mut self) {
+        while let Some(frame) = self.frames.last() {
+            match frame {
+                Frame::Code { code, position, .. } if *position >= code.len() => {},
+                Frame::Try { .. } => {},
+                _ => break,
+            }
+            self.pop_frame();
         }
     }
 
-    // 単一トークンの実行
-    fn execute_single_token(&mut self, token: &Token) -> Result<(), String> {
-        let mut pending_description: Option<String> = None;
-        
-        match token {
-            Token::Description(text) => {
-                pending_description = Some(text.clone());
-            },
-            Token::Number(num, den) => {
-                self.stack.push(Value {
-                    val_type: ValueType::Number(Fraction::new(*num, *den)),
-                });
+    // 先頭のフレームを1ステップ進める
+    fn step_frame(&mut self) -> Result<(), String> {
+        self.consume_step()?;
+        let (code, position) = match self.frames.last_mut() {
+            Some(Frame::Code { code, position, .. }) => {
+                *position += 1;
+                (Rc::clone(code), *position - 1)
             },
-            Token::String(s) => {
-                self.stack.push(Value {
-                    val_type: ValueType::String(s.clone()),
-                });
+            _ => match self.pop_frame() {
+                Some(Frame::Control(control)) => return self.resume_control(control),
+                _ => return Ok(()),
             },
-            Token::Boolean(b) => {
-                self.stack.push(Value {
-                    val_type: ValueType::Boolean(*b),
+        };
+        self.execute_instruction(&code[position])
+    }
+
+    // エラーを最も内側のTRYまで巻き戻す（TRYがなければそのままエラーを返す）
+    fn unwind(&mut self, message: String) -> Result<(), String> {
+        while let Some(frame) = self.pop_frame() {
+            if let Frame::Try { saved_stack, handler } = frame {
+                let payload = self.thrown.take().unwrap_or(Value {
+                    val_type: ValueType::String(message),
                 });
+                self.stack = saved_stack;
+                self.stack.push(Value { val_type: ValueType::Error(Box::new(payload)) });
+                return self.push_code(handler, None);
+            }
+        }
+        Err(message)
+    }
+
+    // 単一命令の実行
+    fn execute_instruction(&mut self, instruction: &Instruction) -> Result<(), String> {
+        self.stats.instructions += 1;
+        match instruction {
+            Instruction::Push(value) => self.stack.push(value.clone()),
+            Instruction::Builtin { name, op, arity } => {
+                if self.force_arguments(*arity)? {
+                    return Ok(());
+                }
+                self.call_builtin(*name, *op)?;
             },
-            Token::Nil => {
-                self.stack.push(Value {
-                    val_type: ValueType::Nil,
-                });
+            Instruction::BuiltinWithPolicy { name, op, policy } => {
+                if self.force_arguments(2)? {
+                    return Ok(());
+                }
+                // この演算に限ってポリシーを切り替える
+                let saved = std::mem::replace(&mut self.broadcast_policy, *policy);
+                let result = self.call_builtin(*name, *op);
+                self.broadcast_policy = saved;
+                result?;
             },
-            Token::VectorStart => {
-                // ベクタを収集（ステップ実行時は一度に処理）
-                let mut depth = 1;
-                let mut vector_tokens = vec![Token::VectorStart];
-                
-                while depth > 0 && self.step_position < self.step_tokens.len() {
-                    let next_token = self.step_tokens[self.step_position].clone();
-                    self.step_position += 1;
-                    
-                    match &next_token {
-                        Token::VectorStart => depth += 1,
-                        Token::VectorEnd => depth -= 1,
-                        _ => {}
-                    }
-                    
-                    vector_tokens.push(next_token);
+            Instruction::Def => {
+                if self.force_arguments(2)? {
+                    return Ok(());
                 }
-                
-                // ベクタをデータとして解析
-                let (vector_values, _) = self.collect_vector_as_data(&vector_tokens)?;
-                self.stack.push(Value {
-                    val_type: ValueType::Vector(vector_values),
-                });
+                let desc = match self.frames.last_mut() {
+                    Some(Frame::Code { description, .. }) => description.take(),
+                    _ => None,
+                };
+                self.op_def_with_comment(desc)?;
             },
-            Token::Symbol(name) => {
-                if matches!(name.as_str(), "+" | "-" | "*" | "/" | ">" | ">=" | "=" | "<" | "<=") {
-                    self.execute_operator(name)?;
-                } else if let Some(def) = self.dictionary.get(name).cloned() {
-                    if def.is_builtin {
-                        if name == "DEF" {
-                            let desc = pending_description.take();
-                            self.op_def_with_comment(desc)?;
-                        } else {
-                            self.execute_builtin(name)?;
-                        }
-                    } else {
-                        // カスタムワードは展開して実行
-                        self.execute_tokens_with_context(&def.tokens)?;
-                    }
-                } else {
-                    return Err(format!("Unknown word: {}", name));
+            Instruction::Describe(text) => {
+                // 次のDEFへ引き継ぐ
+                if let Some(Frame::Code { description, .. }) = self.frames.last_mut() {
+                    *description = Some(text.clone());
                 }
             },
-            Token::VectorEnd => return Err("Unexpected ']' found.".to_string()),
+            Instruction::Call { name, code } => self.call_word(*name, code.clone())?,
+            Instruction::Recurse { name, code } => self.call_word(*name, code.upgrade())?,
+            Instruction::Lift { arity, code, word } => {
+                self.apply_lifted(Rc::clone(code), *arity, Some(*word))?;
+            },
         }
-        
+        self.stats.max_stack_depth = self.stats.max_stack_depth.max(self.stack.len());
         Ok(())
     }
 
+    // カスタムワードは新しいフレームで実行（本体がなければ辞書から引く）
+    fn call_word(&mut self, name: Symbol, code: Option<Quotation>) -> Result<(), String> {
+        let body = match code {
+            Some(code) => code,
+            None => match self.dictionary.get(&name) {
+                Some(def) if !def.is_builtin => Rc::clone(&def.code),
+                _ => return Err(format!("Unknown word: {}", name)),
+            },
+        };
+        trace::log(LogLevel::Trace, "interpreter", || format!("Calling {} (depth {})", name, self.frames.len()));
+        self.stats.word_calls += 1;
+        self.push_code(body, Some(name))
+    }
+
+    // トークン列を命令列にコンパイルする
+    // （definitionは定義中のワードで、依存関係に登録したカスタムワードへの参照を解決する）
+    fn compile(&self, tokens: &[Token], definition: Option<&Definition>) -> Result<Vec<Instruction>, String> {
+        let mut code = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+        while i < tokens.len() {
+            let instruction = match &tokens[i] {
+                Token::Description(text) => Instruction::Describe(text.clone()),
+                Token::Literal(value) => Instruction::Push(value.clone()),
+                Token::Number(num, den) => Instruction::Push(Value {
+                    val_type: ValueType::Number(Fraction::new(*num, *den)),
+                }),
+                Token::String(s) => Instruction::Push(Value { val_type: ValueType::String(s.clone()) }),
+                Token::Boolean(b) => Instruction::Push(Value { val_type: ValueType::Boolean(*b) }),
+                Token::Nil => Instruction::Push(Value { val_type: ValueType::Nil }),
+                Token::VectorStart => {
+                    // ベクタは「データ」としてコンパイル時に構築しておく
+                    let (vector_values, consumed) = self.collect_vector_as_data(&tokens[i..], definition)?;
+                    code.push(Instruction::Push(Value { val_type: ValueType::Vector(vector_values) }));
+                    i += consumed;
+                    continue;
+                },
+                Token::VectorEnd => return Err("Unexpected ']' found.".to_string()),
+                Token::Symbol(name) => self.compile_word(*name, definition)?,
+            };
+            code.push(instruction);
+            i += 1;
+        }
+        Ok(code)
+    }
+
+    // ワードの本体をコンパイルする（要素ごとのワードは引数を調べる命令で包む）
+    fn compile_definition(&self, name: Symbol, tokens: &[Token], elementwise: Option<usize>) -> Result<Quotation, String> {
+        let mut result = Ok(());
+        let code = Rc::new_cyclic(|this| {
+            let definition = Definition { name, code: this.clone() };
+            match self.compile(tokens, Some(&definition)) {
+                Ok(body) => match elementwise {
+                    Some(arity) => vec![Instruction::Lift { arity, code: Rc::new(body), word: name }],
+                    None => body,
+                },
+                Err(message) => {
+                    result = Err(message);
+                    Vec::new()
+                },
+            }
+        });
+        result.map(|_| code)
+    }
+
+    fn compile_word(&self, name: Symbol, definition: Option<&Definition>) -> Result<Instruction, String> {
+        match self.dictionary.get(&name) {
+            Some(def) if def.is_builtin => {
+                let symbol = name;
+                let name = name.as_str();
+                if name == "DEF" {
+                    return Ok(Instruction::Def);
+                }
+                if let Some(op) = Self::builtin_fn(name) {
+                    return Ok(Instruction::Builtin { name: symbol, op, arity: Self::builtin_arity(name) });
+                }
+                match Self::split_policy_word(name).and_then(|(op, policy)| Some((Self::builtin_fn(op)?, policy))) {
+                    Some((op, policy)) => Ok(Instruction::BuiltinWithPolicy { name: symbol, op, policy }),
+                    None => Err(format!("Unknown builtin: {}", name)),
+                }
+            },
+            _ if definition.is_some_and(|definition| definition.name == name) => Ok(Instruction::Recurse {
+                name,
+                code: definition.unwrap().code.clone(),
+            }),
+            // 依存関係に登録されたワードだけは定義し直せないので本体を直接呼ぶ
+            Some(def) if definition.is_some_and(|definition| self.is_dependency(definition.name, name)) => Ok(Instruction::Call {
+                name,
+                code: Some(Rc::clone(&def.code)),
+            }),
+            // 定義の外や未定義だったワード（前方参照）は実行時に辞書から引く
+            _ => Ok(Instruction::Call { name, code: None }),
+        }
+    }
+
+    // userがdepを使っているとして依存関係に登録されているか
+    fn is_dependency(&self, user: Symbol, dep: Symbol) -> bool {
+        self.dependencies.get(&dep).is_some_and(|users| users.contains(&user))
+    }
+
     /// トークンをデータとして解析し、Valueのベクタに変換する（ネスト対応）
-    fn collect_vector_as_data(&self, tokens: &[Token]) -> Result<(Vec<Value>, usize), String> {
+    /// （コードとして実行するときのために、ネストしたものも含めてコンパイルしておく）
+    fn collect_vector_as_data(&self, tokens: &[Token], definition: Option<&Definition>) -> Result<(VectorData, usize), String> {
         let mut values = Vec::new();
         let mut i = 1; // 開始の'['をスキップ
 
@@ -182,11 +864,13 @@ impl Interpreter {
             match &tokens[i] {
                 Token::VectorEnd => {
                     // ベクタの終わり
-                    return Ok((values, i + 1)); // 消費したトークン数を返す
+                    let items = VectorData::from(values);
+                    self.compile_literal(&items, definition)?;
+                    return Ok((items, i + 1)); // 消費したトークン数を返す
                 },
                 Token::VectorStart => {
                     // ネストしたベクタの開始
-                    let (nested_values, consumed) = self.collect_vector_as_data(&tokens[i..])?;
+                    let (nested_values, consumed) = self.collect_vector_as_data(&tokens[i..], definition)?;
                     values.push(Value { val_type: ValueType::Vector(nested_values) });
                     i += consumed; // ネストしたベクタのトークンをスキップ
                     continue;
@@ -196,8 +880,9 @@ impl Interpreter {
                 Token::String(s) => values.push(Value { val_type: ValueType::String(s.clone()) }),
                 Token::Boolean(b) => values.push(Value { val_type: ValueType::Boolean(*b) }),
                 Token::Nil => values.push(Value { val_type: ValueType::Nil }),
-                Token::Symbol(s) => values.push(Value { val_type: ValueType::Symbol(s.clone()) }),
+                Token::Symbol(s) => values.push(Value { val_type: ValueType::Symbol(*s) }),
                 Token::Description(_) => { /* 説明はVectorデータ内では無視 */ },
+                Token::Literal(value) => values.push(value.clone()),
             }
             i += 1;
         }
@@ -205,163 +890,658 @@ impl Interpreter {
         Err("Unclosed vector".to_string())
     }
     
-    fn execute_tokens_with_context(&mut self, tokens: &[Token]) -> Result<(), String> {
-        let mut i = 0;
-        let mut pending_description: Option<String> = None;
-
-        while i < tokens.len() {
-            match &tokens[i] {
-                Token::Description(text) => {
-                    pending_description = Some(text.clone());
-                },
-                Token::Number(num, den) => {
-                    self.stack.push(Value {
-                        val_type: ValueType::Number(Fraction::new(*num, *den)),
-                    });
-                },
-                Token::String(s) => {
-                    self.stack.push(Value {
-                        val_type: ValueType::String(s.clone()),
-                    });
-                },
-                Token::Boolean(b) => {
-                    self.stack.push(Value {
-                        val_type: ValueType::Boolean(*b),
-                    });
-                },
-                Token::Nil => {
-                    self.stack.push(Value {
-                        val_type: ValueType::Nil,
-                    });
-                },
-                Token::VectorStart => {
-                    // ベクタを「データ」として解析し、スタックに積む
-                    let (vector_values, consumed) = self.collect_vector_as_data(&tokens[i..])?;
-                    self.stack.push(Value {
-                        val_type: ValueType::Vector(vector_values),
-                    });
-                    i += consumed - 1; // インデックスを調整
-                },
-                Token::Symbol(name) => {
-                    // シンボルの実行ロジック
-                    if matches!(name.as_str(), "+" | "-" | "*" | "/" | ">" | ">=" | "=" | "<" | "<=") {
-                        self.execute_operator(name)?;
-                    } else if let Some(def) = self.dictionary.get(name).cloned() {
-                        if def.is_builtin {
-                            if name == "DEF" {
-                                let desc = pending_description.take();
-                                self.op_def_with_comment(desc)?;
-                            } else {
-                                self.execute_builtin(name)?;
-                            }
-                        } else {
-                            self.execute_tokens_with_context(&def.tokens)?;
+    // フレームを積む（実行し終えたコードフレームは末尾呼び出しとして置き換える）
+    // （トップレベルのフレームはステップ実行の位置を示すので置き換えない）
+    fn push_frame(&mut self, mut frame: Frame) -> Result<(), String> {
+        if let [_, .., Frame::Code { code, position, .. }] = self.frames.as_slice() {
+            if *position >= code.len() {
+                if let Some(Frame::Code { word: caller, tail_calls: caller_tail_calls, .. }) = self.pop_frame() {
+                    if let Frame::Code { word, tail_calls, .. } = &mut frame {
+                        match (*word, caller) {
+                            // クォーテーションは呼び出し元のワード名と履歴を引き継ぐ
+                            (None, _) => {
+                                *word = caller;
+                                *tail_calls = caller_tail_calls;
+                            },
+                            // 置き換えたワードは履歴に残す
+                            (Some(_), Some(caller)) => {
+                                let mut history = caller_tail_calls.unwrap_or_default();
+                                history.record(caller);
+                                *tail_calls = Some(history);
+                            },
+                            (Some(_), None) => *tail_calls = caller_tail_calls,
                         }
-                    } else {
-                        return Err(format!("Unknown word: {}", name));
                     }
-                },
-                Token::VectorEnd => return Err("Unexpected ']' found.".to_string()),
+                }
             }
-            
-            i += 1;
         }
-        
+        if self.frames.len() >= self.max_call_depth {
+            return Err(self.limit_error(&format!("Call depth limit ({}) exceeded", self.max_call_depth)));
+        }
+        if Self::is_call_frame(&frame) {
+            self.call_depth += 1;
+            self.stats.max_call_depth = self.stats.max_call_depth.max(self.call_depth);
+        }
+        self.frames.push(frame);
         Ok(())
     }
 
-    fn body_vector_to_tokens(
-        &self,
-        body: &[Value],
-    ) -> Result<(Vec<Token>, HashSet<String>), String> {
-        let mut tokens = Vec::new();
-        let mut dependencies = HashSet::new();
+    // 呼び出し履歴に並ぶカスタムワードのフレームか（呼び出しの深さとして数える）
+    fn is_call_frame(frame: &Frame) -> bool {
+        matches!(
+            frame,
+            Frame::Code { word: Some(_), .. } | Frame::Control(Control::Lift(Lifting { word: Some(_), .. }))
+        )
+    }
 
-        for val in body {
-            self.value_to_tokens_recursive(val, &mut tokens, &mut dependencies)?;
+    fn pop_frame(&mut self) -> Option<Frame> {
+        let frame = self.frames.pop();
+        if frame.as_ref().is_some_and(Self::is_call_frame) {
+            self.call_depth -= 1;
         }
+        frame
+    }
 
-        Ok((tokens, dependencies))
+    fn clear_frames(&mut self) {
+        self.frames.clear();
+        self.call_depth = 0;
     }
 
-    fn value_to_tokens_recursive(
-        &self,
-        val: &Value,
-        tokens: &mut Vec<Token>,
-        dependencies: &mut HashSet<String>,
-    ) -> Result<(), String> {
-        match &val.val_type {
-            ValueType::Number(n) => tokens.push(Token::Number(n.numerator, n.denominator)),
-            ValueType::String(s) => tokens.push(Token::String(s.clone())),
-            ValueType::Boolean(b) => tokens.push(Token::Boolean(*b)),
-            ValueType::Nil => tokens.push(Token::Nil),
-            ValueType::Symbol(s) => {
-                tokens.push(Token::Symbol(s.clone()));
-                if let Some(def) = self.dictionary.get(s) {
-                    if !def.is_builtin {
-                        dependencies.insert(s.clone());
-                    }
-                }
-            }
-            ValueType::Vector(v) => {
+    fn push_code(&mut self, code: Quotation, word: Option<Symbol>) -> Result<(), String> {
+        self.push_frame(Frame::Code { code, position: 0, word, description: None, tail_calls: None })
+    }
+
+    fn push_control(&mut self, control: Control) -> Result<(), String> {
+        self.push_frame(Frame::Control(control))
+    }
+
+    // ベクタをコード（クォーテーション）として実行（継続に積み、次のステップから実行する）
+    fn execute_quotation(&mut self, body: &VectorData) -> Result<(), String> {
+        let code = self.compile_quotation(body)?;
+        self.push_code(code, None)
+    }
+
+    // リテラルはコンパイル済みのものを使い、実行中に作ったベクタはその場でコンパイルする
+    fn compile_quotation(&self, body: &VectorData) -> Result<Quotation, String> {
+        let key: *const Vec<Value> = &**body;
+        let definition = match self.compiled_literals.borrow().get(&key) {
+            Some(literal) if literal.definition.is_none() || literal.version == self.dictionary_version => {
+                return Ok(Rc::clone(&literal.code));
+            },
+            Some(literal) => literal.definition.clone(),
+            None => return Ok(Rc::new(self.compile_values(body, None)?)),
+        };
+        let code = Rc::new(self.compile_values(body, definition.as_ref())?);
+        if let Some(literal) = self.compiled_literals.borrow_mut().get_mut(&key) {
+            literal.version = self.dictionary_version;
+            literal.code = Rc::clone(&code);
+        }
+        Ok(code)
+    }
+
+    fn compile_literal(&self, items: &VectorData, definition: Option<&Definition>) -> Result<(), String> {
+        let code = Rc::new(self.compile_values(items, definition)?);
+        self.compiled_literals.borrow_mut().insert(&**items, CompiledLiteral {
+            items: items.downgrade(),
+            definition: definition.cloned(),
+            version: self.dictionary_version,
+            code,
+        });
+        Ok(())
+    }
+
+    // リテラルのコンパイル済みのコード（メモリ使用量の計算用）
+    pub(crate) fn literal_code(&self, items: &VectorData) -> Option<Quotation> {
+        let key: *const Vec<Value> = &**items;
+        self.compiled_literals.borrow().get(&key).map(|literal| Rc::clone(&literal.code))
+    }
+
+    // ベクタの要素を命令列にコンパイルする（ネストしたベクタはそのまま積む）
+    fn compile_values(&self, values: &[Value], definition: Option<&Definition>) -> Result<Vec<Instruction>, String> {
+        values.iter()
+            .map(|value| match &value.val_type {
+                ValueType::Symbol(name) => self.compile_word(*name, definition),
+                _ => Ok(Instruction::Push(value.clone())),
+            })
+            .collect()
+    }
+
+    // 引数にVectorがあれば要素ごとに適用した結果を積み、なければそのまま実行する
+    fn apply_lifted(&mut self, code: Quotation, arity: usize, word: Option<Symbol>) -> Result<(), String> {
+        if self.stack.len() < arity {
+            return Err(format!("Stack underflow for {} (requires {} values)", Self::lift_label(word), arity));
+        }
+        let start = self.stack.len() - arity;
+        if !self.stack[start..].iter().any(|arg| matches!(arg.val_type, ValueType::Vector(_))) {
+            return self.push_code(code, None);
+        }
+        let args = self.stack.split_off(start);
+        let mut calls = Vec::new();
+        let shape = Self::lift_shape(args, self.broadcast_policy, &mut calls)?;
+        let base = self.stack.len();
+        self.push_control(Control::Lift(Lifting {
+            code,
+            word,
+            calls,
+            index: 0,
+            results: Vec::new(),
+            shape,
+            base,
+        }))
+    }
+
+    fn lift_label(word: Option<Symbol>) -> String {
+        word.map_or_else(|| "LIFT quotation".to_string(), |word| word.to_string())
+    }
+
+    // 2項演算子と同じ規則で、ネストしたVectorも再帰的に要素ごとに分けて引数の組を集める
+    fn lift_shape(args: Vec<Value>, policy: BroadcastPolicy, calls: &mut Vec<Vec<Value>>) -> Result<LiftShape, String> {
+        let lens: Vec<usize> = args.iter()
+            .filter_map(|arg| match &arg.val_type {
+                ValueType::Vector(v) => Some(v.len()),
+                _ => None,
+            })
+            .collect();
+        if lens.is_empty() {
+            calls.push(args);
+            return Ok(LiftShape::Apply);
+        }
+        let len = policy.result_len(&lens)?;
+        let mut shapes = Vec::with_capacity(len);
+        for i in 0..len {
+            let elements: Option<Vec<Value>> = args.iter()
+                .map(|arg| match &arg.val_type {
+                    ValueType::Vector(v) => policy.element(v, i).cloned(),
+                    _ => Some(arg.clone()),
+                })
+                .collect();
+            shapes.push(match elements {
+                Some(elements) => Self::lift_shape(elements, policy, calls)?,
+                None => LiftShape::Nil,
+            });
+        }
+        Ok(LiftShape::Vector(shapes))
+    }
+
+    // 前回の適用結果を受け取り、次の引数の組へ適用する
+    fn resume_lifting(&mut self, mut lifting: Lifting) -> Result<(), String> {
+        if lifting.index > 0 {
+            if self.stack.len() != lifting.base + 1 {
+                return Err(format!("{} must leave exactly one value", Self::lift_label(lifting.word)));
+            }
+            lifting.results.push(self.stack.pop().unwrap());
+        }
+        if lifting.index < lifting.calls.len() {
+            let args = std::mem::take(&mut lifting.calls[lifting.index]);
+            self.stack.extend(args);
+            lifting.index += 1;
+            let (code, word) = (Rc::clone(&lifting.code), lifting.word);
+            self.push_control(Control::Lift(lifting))?;
+            return self.push_code(code, word);
+        }
+        let mut results = lifting.results.into_iter();
+        let value = Self::build_lifted(lifting.shape, &mut results);
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn build_lifted(shape: LiftShape, results: &mut std::vec::IntoIter<Value>) -> Value {
+        let val_type = match shape {
+            LiftShape::Apply => return results.next().unwrap(),
+            LiftShape::Nil => ValueType::Nil,
+            LiftShape::Vector(shapes) => ValueType::Vector(
+                shapes.into_iter().map(|shape| Self::build_lifted(shape, results)).collect(),
+            ),
+        };
+        Value { val_type }
+    }
+
+    // 組み込みワードが取り出す引数の数（遅延シーケンスをそのまま受け取るワードは0）
+    fn builtin_arity(name: &str) -> usize {
+        match name {
+            "DUP" | "DROP" | "SWAP" | "OVER" | "ROT" | "NIP" | ">R" | "R>" | "R@" | "DIP" | "KEEP"
+            | "RANGE" | "ITERATE" | "TAKE" | "TAKE-WHILE" | "FORCE" => 0,
+            "CR" | "SPACE" | "STATS" => 0,
+            "LENGTH" | "HEAD" | "TAIL" | "REVERSE" | "UNCONS" | "EMPTY?" | "NIL?" | "COMPACT" | "NOT"
+            | "ALL?" | "ANY?" | "NONE?" | "CALL" | "COND" | "THROW" | "ERROR-MESSAGE" | "ERROR-VALUE"
+            | "DEL" | "." | "PRINT" | "SPACES" | "EMIT" | "BROADCAST-POLICY" => 1,
+            "IF" | "CASE" | "FOLD" | "SCAN" | "ZIP-WITH" | "DO-RANGE" => 3,
+            _ => 2,
+        }
+    }
+
+    // 組み込みワードが取り出す引数のうち要素数が有限のシーケンスを、継続の上でベクトルにする
+    // （trueなら計算を積んだので、終わってから同じ命令をもう一度実行する）
+    // （無限のシーケンスはそのまま残し、各ワードの型エラーとする）
+    fn force_arguments(&mut self, arity: usize) -> Result<bool, String> {
+        let len = self.stack.len();
+        for slot in len.saturating_sub(arity)..len {
+            let seq = match &self.stack[slot].val_type {
+                ValueType::Sequence(seq) if seq.is_finite() => (**seq).clone(),
+                _ => continue,
+            };
+            if let Some(Frame::Code { position, .. }) = self.frames.last_mut() {
+                *position -= 1;
+            }
+            self.start_forcing(seq, Some(slot))?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // 遅延シーケンスの計算を継続に積む
+    fn start_forcing(&mut self, seq: Sequence, slot: Option<usize>) -> Result<(), String> {
+        if !seq.is_finite() && seq.conditions.is_empty() {
+            return Err("Cannot force an infinite sequence (use TAKE or TAKE-WHILE first)".to_string());
+        }
+        let step = match &seq.source {
+            SequenceSource::Iterate { step, .. } => Some(self.compile_quotation(step)?),
+            _ => None,
+        };
+        let conditions = seq.conditions.iter()
+            .map(|condition| self.compile_quotation(condition))
+            .collect::<Result<Vec<_>, String>>()?;
+        let base = self.stack.len();
+        self.push_control(Control::Force(Forcing {
+            source: seq.source,
+            limit: seq.limit,
+            step,
+            conditions,
+            items: Vec::new(),
+            candidate: None,
+            stage: ForceStage::Next,
+            base,
+            slot,
+        }))
+    }
+
+    // 前回のクォーテーションの結果を受け取り、要素を1つ進める
+    fn resume_forcing(&mut self, mut f: Forcing) -> Result<(), String> {
+        let (candidate, condition) = match f.stage {
+            ForceStage::Next => {
+                let index = f.items.len();
+                if f.limit.is_some_and(|limit| index >= limit) {
+                    return self.finish_forcing(f);
+                }
+                let next = match &f.source {
+                    SequenceSource::Range { start, end } => {
+                        let value = start.add(&Fraction::new(index as i64, 1));
+                        if end.as_ref().is_some_and(|end| value.ge(end)) {
+                            return self.finish_forcing(f);
+                        }
+                        Value { val_type: ValueType::Number(value) }
+                    },
+                    SequenceSource::Iterate { seed, .. } => match f.items.last() {
+                        // 直前の要素にクォーテーションを適用する
+                        Some(previous) => {
+                            self.stack.push(previous.clone());
+                            let step = f.step.clone().unwrap();
+                            f.stage = ForceStage::Step;
+                            self.push_control(Control::Force(f))?;
+                            return self.push_code(step, None);
+                        },
+                        None => seed.clone(),
+                    },
+                    SequenceSource::Items(values) => match values.get(index) {
+                        Some(value) => value.clone(),
+                        None => return self.finish_forcing(f),
+                    },
+                };
+                (next, 0)
+            },
+            ForceStage::Step => (self.pop_forcing_result(f.base, "ITERATE")?, 0),
+            ForceStage::Condition(i) => {
+                match self.pop_forcing_result(f.base, "TAKE-WHILE")?.val_type {
+                    ValueType::Boolean(true) => {},
+                    ValueType::Boolean(false) => return self.finish_forcing(f),
+                    _ => return Err("Type error: TAKE-WHILE quotation must leave a boolean".to_string()),
+                }
+                (f.candidate.take().unwrap(), i + 1)
+            },
+        };
+        if condition < f.conditions.len() {
+            self.stack.push(candidate.clone());
+            let cond = Rc::clone(&f.conditions[condition]);
+            f.candidate = Some(candidate);
+            f.stage = ForceStage::Condition(condition);
+            self.push_control(Control::Force(f))?;
+            return self.push_code(cond, None);
+        }
+        // 次の要素は次のステップで計算する
+        f.items.push(candidate);
+        f.stage = ForceStage::Next;
+        self.push_control(Control::Force(f))
+    }
+
+    fn pop_forcing_result(&mut self, base: usize, word: &str) -> Result<Value, String> {
+        if self.stack.len() != base + 1 {
+            return Err(format!("{} quotation must leave exactly one value", word));
+        }
+        Ok(self.stack.pop().unwrap())
+    }
+
+    fn finish_forcing(&mut self, f: Forcing) -> Result<(), String> {
+        let vector = Value { val_type: ValueType::Vector(f.items.into()) };
+        match f.slot {
+            Some(slot) => self.stack[slot] = vector,
+            None => self.stack.push(vector),
+        }
+        Ok(())
+    }
+
+    // 制御構造を再開する（続きがあれば自身を積み直してから本体を積む）
+    fn resume_control(&mut self, control: Control) -> Result<(), String> {
+        match control {
+            Control::Push(value) => {
+                self.stack.push(value);
+                Ok(())
+            },
+            Control::Sequence { steps, index } => {
+                if index >= steps.len() {
+                    return Ok(());
+                }
+                let (value, body) = steps[index].clone();
+                self.push_control(Control::Sequence { steps, index: index + 1 })?;
+                if let Some(value) = value {
+                    self.stack.push(value);
+                }
+                self.push_code(body, None)
+            },
+            Control::Times { body, remaining } => {
+                if remaining <= 0 {
+                    return Ok(());
+                }
+                self.push_control(Control::Times { body: Rc::clone(&body), remaining: remaining - 1 })?;
+                self.push_code(body, None)
+            },
+            Control::While { cond, body, stage } => match stage {
+                LoopStage::Check => {
+                    if !self.pop_loop_condition("WHILE")? {
+                        return Ok(());
+                    }
+                    self.push_control(Control::While { cond, body: Rc::clone(&body), stage: LoopStage::Test })?;
+                    self.push_code(body, None)
+                },
+                _ => {
+                    self.push_control(Control::While { cond: Rc::clone(&cond), body, stage: LoopStage::Check })?;
+                    self.push_code(cond, None)
+                },
+            },
+            Control::Until { body, cond, stage } => match stage {
+                LoopStage::Body => {
+                    self.push_control(Control::Until { body: Rc::clone(&body), cond, stage: LoopStage::Test })?;
+                    self.push_code(body, None)
+                },
+                LoopStage::Test => {
+                    self.push_control(Control::Until { body, cond: Rc::clone(&cond), stage: LoopStage::Check })?;
+                    self.push_code(cond, None)
+                },
+                LoopStage::Check => {
+                    if self.pop_loop_condition("UNTIL")? {
+                        return Ok(());
+                    }
+                    self.push_control(Control::Until { body: Rc::clone(&body), cond, stage: LoopStage::Test })?;
+                    self.push_code(body, None)
+                },
+            },
+            Control::DoRange { body, next, end } => {
+                if next >= end {
+                    return Ok(());
+                }
+                self.push_control(Control::DoRange { body: Rc::clone(&body), next: next + 1, end })?;
+                self.stack.push(Value { val_type: ValueType::Number(Fraction::new(next, 1)) });
+                self.push_code(body, None)
+            },
+            Control::Iterate(iteration) => self.resume_iteration(iteration),
+            Control::Force(forcing) => self.resume_forcing(forcing),
+            Control::Lift(lifting) => self.resume_lifting(lifting),
+            Control::Cond { pairs, index, conditions } => self.resume_cond(pairs, index, conditions),
+        }
+    }
+
+    // 前回の適用結果を受け取り、次の要素へ適用する
+    fn resume_iteration(&mut self, mut it: Iteration) -> Result<(), String> {
+        let word = it.kind.word();
+        if it.index > 0 && it.kind != IterationKind::Each {
+            if self.stack.len() != it.base + 1 {
+                return Err(format!("{} quotation must leave exactly one value", word));
+            }
+            match it.kind {
+                IterationKind::Map | IterationKind::ZipWith => it.results.push(self.stack.pop().unwrap()),
+                IterationKind::Filter | IterationKind::Reject => match self.stack.pop().unwrap().val_type {
+                    ValueType::Boolean(b) => {
+                        if b != (it.kind == IterationKind::Reject) {
+                            it.results.push(it.items[it.index - 1].clone());
+                        }
+                    },
+                    _ => return Err(format!("Type error: {} quotation must leave a boolean", word)),
+                },
+                IterationKind::Scan => it.results.push(self.stack.last().unwrap().clone()),
+                // FOLD・REDUCEの累積値はスタックに置いたまま次の要素へ
+                _ => {},
+            }
+        }
+
+        if it.index < it.items.len() {
+            self.stack.push(it.items[it.index].clone());
+            if it.kind == IterationKind::ZipWith {
+                self.stack.push(it.others[it.index].clone());
+            }
+            it.index += 1;
+            let body = Rc::clone(&it.body);
+            self.push_control(Control::Iterate(it))?;
+            return self.push_code(body, None);
+        }
+
+        match it.kind {
+            IterationKind::Map | IterationKind::Filter | IterationKind::Reject | IterationKind::ZipWith => {
+                self.stack.push(Value { val_type: ValueType::Vector(it.results.into()) });
+            },
+            IterationKind::Scan => {
+                self.stack.pop();
+                self.stack.push(Value { val_type: ValueType::Vector(it.results.into()) });
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    // 前回の条件の結果を受け取り、次の条件または選ばれた本体を実行する
+    fn resume_cond(
+        &mut self,
+        pairs: Vec<(Quotation, Quotation)>,
+        index: usize,
+        mut conditions: Vec<Value>,
+    ) -> Result<(), String> {
+        if index > 0 {
+            let result = match self.stack.pop() {
+                Some(val) => val,
+                None => return Err("Stack underflow in COND condition".to_string()),
+            };
+            match &result.val_type {
+                // スカラーの条件は最初に真となった時点で本体を実行して終了
+                ValueType::Boolean(b) if conditions.is_empty() => {
+                    if *b {
+                        return self.push_code(Rc::clone(&pairs[index - 1].1), None);
+                    }
+                },
+                ValueType::Boolean(_) | ValueType::Vector(_) => conditions.push(result),
+                _ => return Err("Type error: COND condition must leave a boolean or vector of booleans".to_string()),
+            }
+        }
+
+        if index < pairs.len() {
+            let cond = Rc::clone(&pairs[index].0);
+            self.push_control(Control::Cond { pairs, index: index + 1, conditions })?;
+            return self.push_code(cond, None);
+        }
+
+        if conditions.is_empty() {
+            return Ok(());
+        }
+
+        // Vectorの真偽値に対する暗黙の反復（スカラーの条件は全要素に適用）
+        let offset = pairs.len() - conditions.len();
+        let len = conditions.iter()
+            .find_map(|c| match &c.val_type {
+                ValueType::Vector(v) => Some(v.len()),
+                _ => None,
+            })
+            .unwrap_or(0);
+        for c in &conditions {
+            if let ValueType::Vector(v) = &c.val_type {
+                if v.len() != len {
+                    return Err("Vector length mismatch".to_string());
+                }
+            }
+        }
+        let mut steps = Vec::new();
+        for index in 0..len {
+            let selected = conditions.iter().position(|c| match &c.val_type {
+                ValueType::Boolean(b) => *b,
+                ValueType::Vector(v) => matches!(v[index].val_type, ValueType::Boolean(true)),
+                _ => false,
+            });
+            if let Some(j) = selected {
+                steps.push((None, Rc::clone(&pairs[offset + j].1)));
+            }
+        }
+        self.push_control(Control::Sequence { steps, index: 0 })
+    }
+
+    fn body_vector_to_tokens(
+        &self,
+        body: &[Value],
+    ) -> Result<(Vec<Token>, HashSet<Symbol>), String> {
+        let mut tokens = Vec::new();
+        let mut dependencies = HashSet::new();
+
+        for val in body {
+            self.value_to_tokens_recursive(val, &mut tokens, &mut dependencies)?;
+        }
+
+        Ok((tokens, dependencies))
+    }
+
+    fn value_to_tokens_recursive(
+        &self,
+        val: &Value,
+        tokens: &mut Vec<Token>,
+        dependencies: &mut HashSet<Symbol>,
+    ) -> Result<(), String> {
+        match &val.val_type {
+            ValueType::Number(n) => tokens.push(Token::Number(n.numerator, n.denominator)),
+            ValueType::String(s) => tokens.push(Token::String(s.clone())),
+            ValueType::Boolean(b) => tokens.push(Token::Boolean(*b)),
+            ValueType::Nil => tokens.push(Token::Nil),
+            ValueType::Symbol(s) => {
+                tokens.push(Token::Symbol(*s));
+                if let Some(def) = self.dictionary.get(s) {
+                    if !def.is_builtin {
+                        dependencies.insert(*s);
+                    }
+                }
+            }
+            ValueType::Vector(v) => {
                 tokens.push(Token::VectorStart);
                 for item in v {
                     self.value_to_tokens_recursive(item, tokens, dependencies)?;
                 }
                 tokens.push(Token::VectorEnd);
             }
+            ValueType::Error(_) | ValueType::Sequence(_) => tokens.push(Token::Literal(val.clone())),
         }
         Ok(())
     }
         
-    fn execute_builtin(&mut self, name: &str) -> Result<(), String> {
-        match name {
-            "DUP" => self.op_dup(),
-            "DROP" => self.op_drop(),
-            "SWAP" => self.op_swap(),
-            "OVER" => self.op_over(),
-            "ROT" => self.op_rot(),
-            "NIP" => self.op_nip(),
-            ">R" => self.op_to_r(),
-            "R>" => self.op_from_r(),
-            "R@" => self.op_r_fetch(),
-            "DEF" => self.op_def_with_comment(None),
-            "IF" => self.op_if(),
-            "LENGTH" => self.op_length(),
-            "HEAD" => self.op_head(),
-            "TAIL" => self.op_tail(),
-            "CONS" => self.op_cons(),
-            "APPEND" => self.op_append(),
-            "REVERSE" => self.op_reverse(),
-            "NTH" => self.op_nth(),
-            "UNCONS" => self.op_uncons(),
-            "EMPTY?" => self.op_empty(),
-            "DEL" => self.op_del(),
-            "NOT" => self.op_not(),
+    // 組み込みワードの名前から実装を引く（コンパイル時に一度だけ行う）
+    fn builtin_fn(name: &str) -> Option<BuiltinFn> {
+        let op: BuiltinFn = match name {
+            // 演算子
+            "+" => Self::op_add,
+            "-" => Self::op_sub,
+            "*" => Self::op_mul,
+            "/" => Self::op_div,
+            ">" => Self::op_gt,
+            ">=" => Self::op_ge,
+            "=" => Self::op_eq,
+            "<" => Self::op_lt,
+            "<=" => Self::op_le,
+            "DUP" => Self::op_dup,
+            "DROP" => Self::op_drop,
+            "SWAP" => Self::op_swap,
+            "OVER" => Self::op_over,
+            "ROT" => Self::op_rot,
+            "NIP" => Self::op_nip,
+            ">R" => Self::op_to_r,
+            "R>" => Self::op_from_r,
+            "R@" => Self::op_r_fetch,
+            "IF" => Self::op_if,
+            "COND" => Self::op_cond,
+            "CASE" => Self::op_case,
+            "CALL" => Self::op_call,
+            "LET" => Self::op_let,
+            "DIP" => Self::op_dip,
+            "KEEP" => Self::op_keep,
+            "LIFT" => Self::op_lift,
+            "TIMES" => Self::op_times,
+            "WHILE" => Self::op_while,
+            "UNTIL" => Self::op_until,
+            "DO-RANGE" => Self::op_do_range,
+            "MAP" => Self::op_map,
+            "FILTER" => |interpreter| interpreter.op_filter(false),
+            "REJECT" => |interpreter| interpreter.op_filter(true),
+            "FOLD" => Self::op_fold,
+            "REDUCE" => Self::op_reduce,
+            "EACH" => Self::op_each,
+            "SCAN" => Self::op_scan,
+            "ZIP-WITH" => Self::op_zip_with,
+            "RANGE" => Self::op_range,
+            "ITERATE" => Self::op_iterate,
+            "TAKE" => Self::op_take,
+            "TAKE-WHILE" => Self::op_take_while,
+            "FORCE" => Self::op_force,
+            "THROW" => Self::op_throw,
+            "TRY" => Self::op_try,
+            "ERROR-MESSAGE" => Self::op_error_message,
+            "ERROR-VALUE" => Self::op_error_value,
+            "LENGTH" => Self::op_length,
+            "HEAD" => Self::op_head,
+            "TAIL" => Self::op_tail,
+            "CONS" => Self::op_cons,
+            "APPEND" => Self::op_append,
+            "REVERSE" => Self::op_reverse,
+            "NTH" => Self::op_nth,
+            "UNCONS" => Self::op_uncons,
+            "EMPTY?" => Self::op_empty,
+            "NIL?" => Self::op_is_nil,
+            "COALESCE" | "DEFAULT" => Self::op_coalesce,
+            "COMPACT" => Self::op_compact,
+            "FILL-NIL" => Self::op_fill_nil,
+            "DEL" => Self::op_del,
+            "RENAME" => Self::op_rename,
+            "ELEMENTWISE" => Self::op_elementwise,
+            "NOT" => Self::op_not,
+            "AND" => |interpreter| interpreter.op_logic("AND", |a, b| a && b),
+            "OR" => |interpreter| interpreter.op_logic("OR", |a, b| a || b),
+            "XOR" => |interpreter| interpreter.op_logic("XOR", |a, b| a != b),
+            "ALL?" => |interpreter| interpreter.op_quantifier("ALL?"),
+            "ANY?" => |interpreter| interpreter.op_quantifier("ANY?"),
+            "NONE?" => |interpreter| interpreter.op_quantifier("NONE?"),
+            "AND-THEN" => |interpreter| interpreter.op_short_circuit("AND-THEN", false),
+            "OR-ELSE" => |interpreter| interpreter.op_short_circuit("OR-ELSE", true),
             // 出力ワード
-            "." => self.op_dot(),
-            "PRINT" => self.op_print(),
-            "CR" => self.op_cr(),
-            "SPACE" => self.op_space(),
-            "SPACES" => self.op_spaces(),
-            "EMIT" => self.op_emit(),
-            _ => Err(format!("Unknown builtin: {}", name)),
-        }
-    }
-    
-    fn execute_operator(&mut self, op: &str) -> Result<(), String> {
-        match op {
-            "+" => self.op_add(),
-            "-" => self.op_sub(),
-            "*" => self.op_mul(),
-            "/" => self.op_div(),
-            ">" => self.op_gt(),
-            ">=" => self.op_ge(),
-            "=" => self.op_eq(),
-            "<" => self.op_lt(),
-            "<=" => self.op_le(),
-            _ => Err(format!("Unknown operator: {}", op)),
-        }
+            "." => Self::op_dot,
+            "PRINT" => Self::op_print,
+            "CR" => Self::op_cr,
+            "SPACE" => Self::op_space,
+            "SPACES" => Self::op_spaces,
+            "EMIT" => Self::op_emit,
+            "STATS" => Self::op_stats,
+            "BROADCAST-POLICY" => Self::op_broadcast_policy,
+            _ => return None,
+        };
+        Some(op)
     }
     
     fn op_def_with_comment(&mut self, description: Option<String>) -> Result<(), String> {
@@ -374,7 +1554,7 @@ impl Interpreter {
     
         match (&name_val.val_type, &body_val.val_type) {
             (ValueType::String(name), ValueType::Vector(body)) => {
-                let name = name.to_uppercase();
+                let name = Symbol::intern(&name.to_uppercase());
     
                 if let Some(existing) = self.dictionary.get(&name) {
                     if existing.is_builtin {
@@ -383,9 +1563,10 @@ impl Interpreter {
                 }
     
                 if self.dictionary.contains_key(&name) {
+                    self.dictionary_version += 1;
                     if let Some(dependents) = self.dependencies.get(&name) {
                         if !dependents.is_empty() {
-                            let dependent_list: Vec<String> = dependents.iter().cloned().collect();
+                            let dependent_list: Vec<String> = dependents.iter().map(|dep| dep.to_string()).collect();
                             return Err(format!(
                                 "Cannot redefine '{}' because it is used by: {}",
                                 name,
@@ -398,7 +1579,7 @@ impl Interpreter {
                         let mut old_deps = HashSet::new();
                         for token in &old_def.tokens {
                            if let Token::Symbol(s) = token {
-                               old_deps.insert(s.clone());
+                               old_deps.insert(*s);
                            }
                         }
 
@@ -414,16 +1595,20 @@ impl Interpreter {
     
                 for dep_name in &new_dependencies {
                     self.dependencies
-                        .entry(dep_name.clone())
-                        .or_insert_with(HashSet::new)
-                        .insert(name.clone());
+                        .entry(*dep_name)
+                        .or_default()
+                        .insert(name);
                 }
     
-                self.dictionary.insert(name.clone(), WordDefinition {
+                let code = self.compile_definition(name, &new_tokens, None)?;
+                self.dictionary.insert(name, WordDefinition {
                     tokens: new_tokens,
+                    code,
                     is_builtin: false,
                     description,
+                    elementwise: None,
                 });
+                trace::log(LogLevel::Info, "interpreter", || format!("Defined {}", name));
     
                 Ok(())
             }
@@ -432,7 +1617,8 @@ impl Interpreter {
     }
 
     pub fn delete_word(&mut self, name: &str) -> Result<(), String> {
-        if let Some(def) = self.dictionary.get(name) {
+        let name = Symbol::intern(name);
+        if let Some(def) = self.dictionary.get(&name) {
             if def.is_builtin {
                 return Err(format!("Cannot delete builtin word: {}", name));
             }
@@ -440,9 +1626,9 @@ impl Interpreter {
             return Err(format!("Word not found: {}", name));
         }
         
-        if let Some(dependents) = self.dependencies.get(name) {
+        if let Some(dependents) = self.dependencies.get(&name) {
             if !dependents.is_empty() {
-                let dependent_list: Vec<String> = dependents.iter().cloned().collect();
+                let dependent_list: Vec<String> = dependents.iter().map(|dep| dep.to_string()).collect();
                 return Err(format!(
                     "Cannot delete '{}' because it is used by: {}", 
                     name, 
@@ -451,16 +1637,124 @@ impl Interpreter {
             }
         }
         
-        self.dictionary.remove(name);
+        self.dictionary.remove(&name);
+        self.dictionary_version += 1;
         
         for (_, deps) in self.dependencies.iter_mut() {
-            deps.remove(name);
+            deps.remove(&name);
+        }
+        
+        self.dependencies.remove(&name);
+        
+        Ok(())
+    }
+
+    // カスタムワードの名前を変更し、依存するワードの定義も書き換える
+    pub fn rename_word(&mut self, old_name: &str, new_name: &str) -> Result<(), String> {
+        // 新しい名前はそれだけで1つのワードとして読めなければ呼び出せない
+        if !matches!(tokenize(new_name).as_deref(), Ok([Token::Symbol(s)]) if s.as_str() == new_name) {
+            return Err(format!("Invalid word name: \"{}\"", new_name));
+        }
+        let (old_name, new_name) = (Symbol::intern(old_name), Symbol::intern(new_name));
+        match self.dictionary.get(&old_name) {
+            Some(def) if def.is_builtin => {
+                return Err(format!("Cannot rename builtin word: {}", old_name));
+            },
+            Some(_) => {},
+            None => return Err(format!("Word not found: {}", old_name)),
+        }
+        
+        if old_name == new_name {
+            return Ok(());
+        }
+        
+        if let Some(existing) = self.dictionary.get(&new_name) {
+            if existing.is_builtin {
+                return Err(format!("Cannot rename to builtin word: {}", new_name));
+            }
+            return Err(format!("Cannot rename '{}' because '{}' already exists", old_name, new_name));
+        }
+        
+        let def = self.dictionary.remove(&old_name).unwrap();
+        self.dictionary.insert(new_name, def);
+        
+        // 旧名を参照しているすべての定義（再帰呼び出しを含む）を書き換える
+        let mut rewritten = vec![new_name];
+        for (name, def) in self.dictionary.iter_mut().filter(|(_, def)| !def.is_builtin) {
+            let mut changed = false;
+            for token in def.tokens.iter_mut() {
+                if let Token::Symbol(s) = token {
+                    if *s == old_name {
+                        *s = new_name;
+                        changed = true;
+                    }
+                }
+            }
+            if changed && *name != new_name {
+                rewritten.push(*name);
+            }
+        }
+        
+        // 依存関係マップのキーと値の両方を新しい名前に付け替える
+        if let Some(dependents) = self.dependencies.remove(&old_name) {
+            self.dependencies.insert(new_name, dependents);
+        }
+        for deps in self.dependencies.values_mut() {
+            if deps.remove(&old_name) {
+                deps.insert(new_name);
+            }
         }
         
-        self.dependencies.remove(name);
+        // 書き換えた定義は新しいトークンから依存関係を求め直し、コンパイルし直す
+        for name in &rewritten {
+            self.refresh_dependencies(*name);
+        }
+        for name in rewritten {
+            self.recompile(name)?;
+        }
         
         Ok(())
     }
+
+    // 定義のトークンが参照しているカスタムワードの依存関係に登録し直す
+    // （自身の再帰呼び出しはRecurseで解決するので含めない）
+    fn refresh_dependencies(&mut self, name: Symbol) {
+        for deps in self.dependencies.values_mut() {
+            deps.remove(&name);
+        }
+        let used: HashSet<Symbol> = match self.dictionary.get(&name) {
+            Some(def) => def.tokens.iter()
+                .filter_map(|token| match token {
+                    Token::Symbol(s) if *s != name => Some(*s),
+                    _ => None,
+                })
+                .filter(|s| self.dictionary.get(s).is_some_and(|def| !def.is_builtin))
+                .collect(),
+            None => return,
+        };
+        for dep in used {
+            self.dependencies.entry(dep).or_default().insert(name);
+        }
+    }
+
+    // 定義をコンパイルし直し、それを呼び出している定義にも反映する
+    fn recompile(&mut self, name: Symbol) -> Result<(), String> {
+        let code = match self.dictionary.get(&name) {
+            Some(def) if !def.is_builtin => self.compile_definition(name, &def.tokens, def.elementwise)?,
+            _ => return Ok(()),
+        };
+        if let Some(def) = self.dictionary.get_mut(&name) {
+            def.code = code;
+        }
+        self.dictionary_version += 1;
+        let dependents: Vec<Symbol> = self.dependencies.get(&name)
+            .map(|deps| deps.iter().filter(|dep| **dep != name).copied().collect())
+            .unwrap_or_default();
+        for dependent in dependents {
+            self.recompile(dependent)?;
+        }
+        Ok(())
+    }
     
     fn op_dup(&mut self) -> Result<(), String> {
         if let Some(top) = self.stack.last() {
@@ -550,693 +1844,996 @@ impl Interpreter {
     
     // 暗黙の反復を実装した新しい演算子
     fn op_add(&mut self) -> Result<(), String> {
+        self.op_arithmetic("+", |n1, n2| Ok(n1.add(n2)))
+    }
+
+    fn op_sub(&mut self) -> Result<(), String> {
+        self.op_arithmetic("-", |n1, n2| Ok(n1.sub(n2)))
+    }
+
+    fn op_mul(&mut self) -> Result<(), String> {
+        self.op_arithmetic("*", |n1, n2| Ok(n1.mul(n2)))
+    }
+
+    fn op_div(&mut self) -> Result<(), String> {
+        self.op_arithmetic("/", |n1, n2| n1.div(n2))
+    }
+
+    fn op_gt(&mut self) -> Result<(), String> {
+        self.op_comparison(">", |n1, n2| n1.gt(n2))
+    }
+
+    fn op_ge(&mut self) -> Result<(), String> {
+        self.op_comparison(">=", |n1, n2| n1.ge(n2))
+    }
+
+    fn op_lt(&mut self) -> Result<(), String> {
+        self.op_comparison("<", |n1, n2| n1.lt(n2))
+    }
+
+    fn op_le(&mut self) -> Result<(), String> {
+        self.op_comparison("<=", |n1, n2| n1.le(n2))
+    }
+
+    // 数値の四則演算（NILは欠損値として伝播し、緩いモードでは数値でない要素を元のまま残す）
+    fn op_arithmetic(&mut self, word: &str, op: fn(&Fraction, &Fraction) -> Result<Fraction, String>) -> Result<(), String> {
+        let strict = self.strict_mode;
+        self.op_broadcast(|x, y| match (&x.val_type, &y.val_type) {
+            (ValueType::Number(n1), ValueType::Number(n2)) => Ok(Some(Value { val_type: ValueType::Number(op(n1, n2)?) })),
+            (ValueType::Nil, _) | (_, ValueType::Nil) => Ok(Some(Value { val_type: ValueType::Nil })),
+            _ if strict => Err(type_mismatch(word, x, y)),
+            _ => Ok(None),
+        })
+    }
+
+    // 数値の比較（NILとの比較はNIL、緩いモードでは数値でない要素をfalseとする）
+    fn op_comparison(&mut self, word: &str, op: fn(&Fraction, &Fraction) -> bool) -> Result<(), String> {
+        let strict = self.strict_mode;
+        self.op_broadcast(|x, y| match (&x.val_type, &y.val_type) {
+            (ValueType::Number(n1), ValueType::Number(n2)) => Ok(Some(Value { val_type: ValueType::Boolean(op(n1, n2)) })),
+            (ValueType::Nil, _) | (_, ValueType::Nil) => Ok(Some(Value { val_type: ValueType::Nil })),
+            _ if strict => Err(type_mismatch(word, x, y)),
+            _ => Ok(Some(Value { val_type: ValueType::Boolean(false) })),
+        })
+    }
+
+    // 数値の2項演算子の共通処理（緩いモードでは型が合わなければ左の値を残し、
+    // VectorとスカラーはスカラーがNumberかNILのときだけ要素ごとに適用する）
+    fn op_broadcast<F>(&mut self, scalar: F) -> Result<(), String>
+    where
+        F: Fn(&Value, &Value) -> Result<Option<Value>, String>,
+    {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let b = self.stack.pop().unwrap();
+        let a = self.stack.pop().unwrap();
+
+        let numeric = |value: &Value| matches!(value.val_type, ValueType::Number(_) | ValueType::Nil | ValueType::Vector(_));
+        let broadcastable = self.strict_mode || (numeric(&a) && numeric(&b));
+        let result = match (&a.val_type, &b.val_type) {
+            (ValueType::Vector(_), _) | (_, ValueType::Vector(_)) if broadcastable => Self::broadcast(&a, &b, self.broadcast_policy, &scalar)?,
+            (ValueType::Vector(_), _) | (_, ValueType::Vector(_)) => None,
+            (ValueType::Number(_), ValueType::Number(_))
+            | (ValueType::Nil, _)
+            | (_, ValueType::Nil) => scalar(&a, &b)?,
+            _ if self.strict_mode => scalar(&a, &b)?,
+            _ => None,
+        };
+        self.stack.push(result.unwrap_or(a));
+        Ok(())
+    }
+
+    // 暗黙の反復（ネストしたVectorも再帰的にたどり、スカラーは各要素へ配る）
+    // （scalarがNoneを返したら型が合わないので、左の値かスカラーを配った先の要素を残す）
+    fn broadcast<F>(a: &Value, b: &Value, policy: BroadcastPolicy, scalar: &F) -> Result<Option<Value>, String>
+    where
+        F: Fn(&Value, &Value) -> Result<Option<Value>, String>,
+    {
+        let result = match (&a.val_type, &b.val_type) {
+            // Vector同士（要素ごと、長さが異なればポリシーに従う）
+            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
+                let len = policy.result_len(&[v1.len(), v2.len()])?;
+                (0..len)
+                    .map(|i| match (policy.element(v1, i), policy.element(v2, i)) {
+                        (Some(x), Some(y)) => Ok(Self::broadcast(x, y, policy, scalar)?.unwrap_or_else(|| x.clone())),
+                        // 短い方に対応する要素がない位置はNIL
+                        _ => Ok(Value { val_type: ValueType::Nil }),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?
+            },
+            // Vectorとスカラー（ブロードキャスト）
+            (ValueType::Vector(v), _) => v.iter()
+                .map(|x| Ok(Self::broadcast(x, b, policy, scalar)?.unwrap_or_else(|| x.clone())))
+                .collect::<Result<Vec<_>, String>>()?,
+            (_, ValueType::Vector(v)) => v.iter()
+                .map(|y| Ok(Self::broadcast(a, y, policy, scalar)?.unwrap_or_else(|| y.clone())))
+                .collect::<Result<Vec<_>, String>>()?,
+            _ => return scalar(a, b),
+        };
+        Ok(Some(Value { val_type: ValueType::Vector(result.into()) }))
+    }
+
+    // セッションのブロードキャストポリシーを設定する
+    fn op_broadcast_policy(&mut self) -> Result<(), String> {
+        match self.stack.pop() {
+            Some(Value { val_type: ValueType::String(name) }) => match BroadcastPolicy::from_name(&name) {
+                Some(policy) => {
+                    self.broadcast_policy = policy;
+                    Ok(())
+                },
+                None => Err(format!("Unknown broadcast policy: {} (use ERROR, CYCLE, PAD or TRUNCATE)", name)),
+            },
+            Some(_) => Err("Type error: BROADCAST-POLICY requires a string".to_string()),
+            None => Err("Stack underflow".to_string()),
+        }
+    }
+
+    // +CYCLEやAND-PADを演算子とポリシーに分ける（辞書に登録された名前だけがここに来る）
+    fn split_policy_word(name: &str) -> Option<(&str, BroadcastPolicy)> {
+        ["CYCLE", "PAD", "TRUNCATE"].into_iter().find_map(|suffix| {
+            let op = name.strip_suffix(suffix)?;
+            let op = match op.strip_suffix('-') {
+                Some(word @ ("AND" | "OR" | "XOR")) => word,
+                _ => op,
+            };
+            Some((op, BroadcastPolicy::from_name(suffix)?))
+        })
+    }
+
+    fn op_eq(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
         let b = self.stack.pop().unwrap();
         let a = self.stack.pop().unwrap();
         
-        web_sys::console::log_1(&format!("op_add: a={:?}, b={:?}", a, b).into());
-        
+        // =演算子はVectorの比較もサポートするが、暗黙の反復も行う
         match (&a.val_type, &b.val_type) {
-            // スカラー + スカラー（従来通り）
+            // スカラー同士（従来通り）
             (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Number(n1.add(n2)) });
+                self.stack.push(Value { val_type: ValueType::Boolean(n1.eq(n2)) });
                 Ok(())
             },
-            // Vector + スカラー（ブロードキャスト）
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(en.add(n))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
+            (ValueType::String(s1), ValueType::String(s2)) => {
+                self.stack.push(Value { val_type: ValueType::Boolean(s1 == s2) });
                 Ok(())
             },
-            // スカラー + Vector（ブロードキャスト）
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(n.add(en))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            // Vector + Vector（要素ごと）
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
-                }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Number(n1.add(n2))
-                        },
-                        _ => a.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
+            (ValueType::Boolean(b1), ValueType::Boolean(b2)) => {
+                self.stack.push(Value { val_type: ValueType::Boolean(b1 == b2) });
                 Ok(())
             },
-            // その他の型の組み合わせは元のまま返す（エラーにはしない）
-            _ => {
-                self.stack.push(a);
-                Ok(())
-            }
-        }
-    }
-    
-    fn op_sub(&mut self) -> Result<(), String> {
-        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Number(n1.sub(n2)) });
+            (ValueType::Symbol(s1), ValueType::Symbol(s2)) => {
+                self.stack.push(Value { val_type: ValueType::Boolean(s1 == s2) });
                 Ok(())
             },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(en.sub(n))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
+            // NILとの比較はほかの比較演算子と同じくNIL（NILかどうかはNIL?で調べる）
+            (ValueType::Nil, _) | (_, ValueType::Nil) => {
+                self.stack.push(Value { val_type: ValueType::Nil });
                 Ok(())
             },
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(n.sub(en))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
+            (ValueType::Error(p1), ValueType::Error(p2)) => {
+                self.stack.push(Value { val_type: ValueType::Boolean(p1 == p2) });
                 Ok(())
             },
+            // Vector全体の比較
             (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
+                if v1.len() == v2.len() && v1 == v2 {
+                    self.stack.push(Value { val_type: ValueType::Boolean(true) });
+                } else {
+                    self.stack.push(Value { val_type: ValueType::Boolean(false) });
                 }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Number(n1.sub(n2))
-                        },
-                        _ => a.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
                 Ok(())
             },
+            // 異なる型の場合はfalse
             _ => {
-                self.stack.push(a);
+                self.stack.push(Value { val_type: ValueType::Boolean(false) });
                 Ok(())
+            },
+        }
+    }
+    
+    fn op_length(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(v) => {
+                    self.stack.push(Value { val_type: ValueType::Number(Fraction::new(v.len() as i64, 1)) });
+                    Ok(())
+                },
+                _ => Err("Type error: LENGTH requires a vector".to_string()),
             }
+        } else {
+            Err("Stack underflow".to_string())
         }
     }
     
-    fn op_mul(&mut self) -> Result<(), String> {
-        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        web_sys::console::log_1(&format!("op_mul: a={:?}, b={:?}", a, b).into());
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Number(n1.mul(n2)) });
-                Ok(())
-            },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(en.mul(n))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(n.mul(en))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
-                }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Number(n1.mul(n2))
-                        },
-                        _ => a.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            _ => {
-                self.stack.push(a);
-                Ok(())
+    fn op_head(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(v) => {
+                    if let Some(first) = v.first() {
+                        self.stack.push(first.clone());
+                        Ok(())
+                    } else {
+                        Err("HEAD of empty vector".to_string())
+                    }
+                },
+                _ => Err("Type error: HEAD requires a vector".to_string()),
             }
+        } else {
+            Err("Stack underflow".to_string())
         }
     }
     
-    fn op_div(&mut self) -> Result<(), String> {
+    fn op_tail(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(v) => {
+                    if v.is_empty() {
+                        Err("TAIL of empty vector".to_string())
+                    } else {
+                        let tail: Vec<Value> = v.into_iter().skip(1).collect();
+                        self.stack.push(Value { val_type: ValueType::Vector(tail.into()) });
+                        Ok(())
+                    }
+                },
+                _ => Err("Type error: TAIL requires a vector".to_string()),
+            }
+        } else {
+            Err("Stack underflow".to_string())
+        }
+    }
+    
+    fn op_cons(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Number(n1.div(n2)) });
+        let vec_val = self.stack.pop().unwrap();
+        let elem = self.stack.pop().unwrap();
+        match vec_val.val_type {
+            ValueType::Vector(mut v) => {
+                v.insert(0, elem);
+                self.stack.push(Value { val_type: ValueType::Vector(v) });
                 Ok(())
             },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(en.div(n))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
+            _ => Err("Type error: CONS requires an element and a vector".to_string()),
+        }
+    }
+
+    fn op_append(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let elem = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match vec_val.val_type {
+            ValueType::Vector(mut v) => {
+                v.push(elem);
+                self.stack.push(Value { val_type: ValueType::Vector(v) });
                 Ok(())
             },
+            _ => Err("Type error: APPEND requires a vector and an element".to_string()),
+        }
+    }
+    
+    fn op_reverse(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(mut v) => {
+                    v.reverse();
+                    self.stack.push(Value { val_type: ValueType::Vector(v) });
+                    Ok(())
+                },
+                _ => Err("Type error: REVERSE requires a vector".to_string()),
+            }
+        } else {
+            Err("Stack underflow".to_string())
+        }
+    }
+
+    fn op_nth(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let vec_val = self.stack.pop().unwrap();
+        let index_val = self.stack.pop().unwrap();
+        match (&index_val.val_type, &vec_val.val_type) {
             (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Number(n.div(en))
-                        },
-                        _ => elem.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
-                }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Number(n1.div(n2))
-                        },
-                        _ => a.clone()
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
+                if n.denominator != 1 { return Err("NTH requires an integer index".to_string()); }
+                let mut index = n.numerator;
+                let len = v.len() as i64;
+                if index < 0 { index += len; }
+                if index < 0 || index >= len { return Err(format!("Index {} out of bounds for vector of length {}", n.numerator, len)); }
+                self.stack.push(v[index as usize].clone());
                 Ok(())
             },
-            _ => {
-                self.stack.push(a);
-                Ok(())
+            _ => Err("Type error: NTH requires a number and a vector".to_string()),
+        }
+    }
+    
+    fn op_uncons(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(v) => {
+                    if v.is_empty() { return Err("UNCONS of empty vector".to_string()); }
+                    let mut v_mut = v;
+                    let head = v_mut.remove(0);
+                    self.stack.push(head);
+                    self.stack.push(Value { val_type: ValueType::Vector(v_mut) });
+                    Ok(())
+                },
+                _ => Err("Type error: UNCONS requires a vector".to_string()),
             }
+        } else {
+            Err("Stack underflow".to_string())
+        }
+    }
+        
+    fn op_empty(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(v) => {
+                    self.stack.push(Value { val_type: ValueType::Boolean(v.is_empty()) });
+                    Ok(())
+                },
+                _ => Err("Type error: EMPTY? requires a vector".to_string()),
+            }
+        } else {
+            Err("Stack underflow".to_string())
         }
     }
     
-    // 比較演算子も暗黙の反復に対応
-    fn op_gt(&mut self) -> Result<(), String> {
+    fn op_is_nil(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            let is_nil = matches!(val.val_type, ValueType::Nil);
+            self.stack.push(Value { val_type: ValueType::Boolean(is_nil) });
+            Ok(())
+        } else {
+            Err("Stack underflow".to_string())
+        }
+    }
+
+    // 値がNILなら代わりの値を使う
+    fn op_coalesce(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(n1.gt(n2)) });
+        let fallback = self.stack.pop().unwrap();
+        let val = self.stack.pop().unwrap();
+        match val.val_type {
+            ValueType::Nil => self.stack.push(fallback),
+            _ => self.stack.push(val),
+        }
+        Ok(())
+    }
+
+    // NILの要素を取り除く
+    fn op_compact(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(v) => {
+                    let compacted = v.into_iter()
+                        .filter(|elem| !matches!(elem.val_type, ValueType::Nil))
+                        .collect();
+                    self.stack.push(Value { val_type: ValueType::Vector(compacted) });
+                    Ok(())
+                },
+                _ => Err("Type error: COMPACT requires a vector".to_string()),
+            }
+        } else {
+            Err("Stack underflow".to_string())
+        }
+    }
+
+    // NILの要素を指定の値で埋める（ネストしたVectorにも再帰的に適用）
+    fn op_fill_nil(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let fill = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        fn fill_nil(val: Value, fill: &Value) -> Value {
+            match val.val_type {
+                ValueType::Nil => fill.clone(),
+                ValueType::Vector(v) => Value {
+                    val_type: ValueType::Vector(v.into_iter().map(|elem| fill_nil(elem, fill)).collect()),
+                },
+                _ => val,
+            }
+        }
+        match vec_val.val_type {
+            ValueType::Vector(_) => {
+                self.stack.push(fill_nil(vec_val, &fill));
                 Ok(())
             },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(en.gt(n))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+            _ => Err("Type error: FILL-NIL requires a vector and a value".to_string()),
+        }
+    }
+
+    // IFワードに暗黙の反復を追加
+    fn op_if(&mut self) -> Result<(), String> {
+        if self.stack.len() < 3 {
+            return Err("Stack underflow for IF".to_string());
+        }
+
+        let else_branch = self.stack.pop().unwrap();
+        let then_branch = self.stack.pop().unwrap();
+        let condition = self.stack.pop().unwrap();
+
+        trace::log(LogLevel::Debug, "interpreter", || format!("IF: condition={}, then={}, else={}",
+                                                              condition, then_branch, else_branch));
+
+        match (&condition.val_type, &then_branch.val_type, &else_branch.val_type) {
+            // 通常のIF（スカラーの真偽値）
+            (ValueType::Boolean(cond), ValueType::Vector(then_vec), ValueType::Vector(else_vec)) => {
+                let vec_to_execute = if *cond { then_vec } else { else_vec };
+                self.execute_quotation(vec_to_execute)
             },
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(n.gt(en))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
+            // Vectorの真偽値に対する暗黙の反復
+            (ValueType::Vector(cond_vec), ValueType::Vector(then_vec), ValueType::Vector(else_vec)) => {
+                let then_tokens = self.compile_quotation(then_vec)?;
+                let else_tokens = self.compile_quotation(else_vec)?;
+                // 各条件に対してIFを実行（真偽値でない要素はスキップ）
+                let steps = cond_vec.iter()
+                    .filter_map(|cond_val| match cond_val.val_type {
+                        ValueType::Boolean(true) => Some((None, Rc::clone(&then_tokens))),
+                        ValueType::Boolean(false) => Some((None, Rc::clone(&else_tokens))),
+                        _ => None,
                     })
                     .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+                self.push_control(Control::Sequence { steps, index: 0 })
             },
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
+            _ => Err("Type error: IF requires a boolean (or vector of booleans) and two vectors".to_string()),
+        }
+    }
+
+    // [ 条件 本体 ] の組のベクトルを取り出す
+    fn parse_pairs(pairs: &[Value], word: &str) -> Result<Vec<(Value, VectorData)>, String> {
+        pairs.iter()
+            .map(|pair| match &pair.val_type {
+                ValueType::Vector(items) if items.len() == 2 => match &items[1].val_type {
+                    ValueType::Vector(body) => Ok((items[0].clone(), body.clone())),
+                    _ => Err(format!("Type error: {} body must be a vector", word)),
+                },
+                _ => Err(format!("Type error: {} requires a vector of pairs", word)),
+            })
+            .collect()
+    }
+
+    // 最初に真となった条件の本体を実行する多分岐
+    fn op_cond(&mut self) -> Result<(), String> {
+        let pairs_val = match self.stack.pop() {
+            Some(val) => val,
+            None => return Err("Stack underflow for COND".to_string()),
+        };
+        let pairs = match &pairs_val.val_type {
+            ValueType::Vector(v) => Self::parse_pairs(v, "COND")?,
+            _ => return Err("Type error: COND requires a vector of [ condition body ] pairs".to_string()),
+        };
+
+        let mut compiled = Vec::with_capacity(pairs.len());
+        for (cond, body) in &pairs {
+            let cond_body = match &cond.val_type {
+                ValueType::Vector(c) => c,
+                _ => return Err("Type error: COND condition must be a vector".to_string()),
+            };
+            compiled.push((self.compile_quotation(cond_body)?, self.compile_quotation(body)?));
+        }
+        self.push_control(Control::Cond { pairs: compiled, index: 0, conditions: Vec::new() })
+    }
+
+    // 値と等しいキーの本体を実行（一致しなければ値を積んだままデフォルトを実行）
+    fn op_case(&mut self) -> Result<(), String> {
+        if self.stack.len() < 3 {
+            return Err("Stack underflow for CASE".to_string());
+        }
+
+        let default_val = self.stack.pop().unwrap();
+        let cases_val = self.stack.pop().unwrap();
+        let value = self.stack.pop().unwrap();
+
+        match (&cases_val.val_type, &default_val.val_type) {
+            (ValueType::Vector(cases), ValueType::Vector(default)) => {
+                let mut compiled = Vec::new();
+                for (key, body) in Self::parse_pairs(cases, "CASE")? {
+                    compiled.push((key, self.compile_quotation(&body)?));
                 }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Boolean(n1.gt(n2))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+                let default = self.compile_quotation(default)?;
+                let steps = match &value.val_type {
+                    // Vectorの値に対する暗黙の反復
+                    ValueType::Vector(values) => values.iter()
+                        .map(|elem| Self::dispatch_case(elem, &compiled, &default))
+                        .collect(),
+                    _ => vec![Self::dispatch_case(&value, &compiled, &default)],
+                };
+                self.push_control(Control::Sequence { steps, index: 0 })
             },
-            _ => {
-                self.stack.push(a);
-                Ok(())
+            _ => Err("Type error: CASE requires a value, a vector of [ key body ] pairs and a vector".to_string()),
+        }
+    }
+
+    fn dispatch_case(
+        value: &Value,
+        cases: &[(Value, Quotation)],
+        default: &Quotation,
+    ) -> (Option<Value>, Quotation) {
+        match cases.iter().find(|(key, _)| key == value) {
+            Some((_, body)) => (None, Rc::clone(body)),
+            None => (Some(value.clone()), Rc::clone(default)),
+        }
+    }
+
+    // 名前のベクタの数だけ値を取り出し、本体中の名前を値に置き換えて実行する
+    // （束縛は本体の字句的な範囲にのみ有効で、呼び出したワードからは見えない）
+    fn op_let(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let body_val = self.stack.pop().unwrap();
+        let names_val = self.stack.pop().unwrap();
+        let (names, body) = match (&names_val.val_type, &body_val.val_type) {
+            (ValueType::Vector(names), ValueType::Vector(body)) => (names, body),
+            _ => return Err("Type error: LET requires two vectors".to_string()),
+        };
+
+        let mut name_list = Vec::with_capacity(names.len());
+        for name in names {
+            match &name.val_type {
+                ValueType::Symbol(s) => name_list.push(*s),
+                _ => return Err("Type error: LET names must be symbols".to_string()),
             }
         }
+        if self.stack.len() < name_list.len() {
+            return Err(format!("Stack underflow for LET (requires {} values)", name_list.len()));
+        }
+
+        let values = self.stack.split_off(self.stack.len() - name_list.len());
+        let bindings: HashMap<Symbol, Value> = name_list.into_iter().zip(values).collect();
+
+        let mut tokens = Vec::new();
+        self.let_body_to_tokens(body, &bindings, &mut tokens);
+        let code = self.compile(&tokens, None)?;
+        self.push_code(Rc::new(code), None)
     }
-    
-    fn op_ge(&mut self) -> Result<(), String> {
+
+    fn let_body_to_tokens(&self, body: &[Value], bindings: &HashMap<Symbol, Value>, tokens: &mut Vec<Token>) {
+        let mut i = 0;
+        while i < body.len() {
+            // 内側のLETは名前のベクタを置き換えず、同名の束縛を隠す
+            if let (Some(names), Some(inner), Some(word)) = (body.get(i), body.get(i + 1), body.get(i + 2)) {
+                if let (ValueType::Vector(names), ValueType::Vector(inner), ValueType::Symbol(word)) =
+                    (&names.val_type, &inner.val_type, &word.val_type)
+                {
+                    if word.as_str() == "LET" {
+                        let mut shadowed = bindings.clone();
+                        for name in names {
+                            if let ValueType::Symbol(s) = &name.val_type {
+                                shadowed.remove(s);
+                            }
+                        }
+                        tokens.push(Token::VectorStart);
+                        self.let_body_to_tokens(names, &HashMap::new(), tokens);
+                        tokens.push(Token::VectorEnd);
+                        tokens.push(Token::VectorStart);
+                        self.let_body_to_tokens(inner, &shadowed, tokens);
+                        tokens.push(Token::VectorEnd);
+                        tokens.push(Token::Symbol(*word));
+                        i += 3;
+                        continue;
+                    }
+                }
+            }
+            
+            let val = &body[i];
+            match &val.val_type {
+                ValueType::Symbol(s) if bindings.contains_key(s) => {
+                    tokens.push(Token::Literal(bindings[s].clone()));
+                },
+                ValueType::Vector(v) => {
+                    tokens.push(Token::VectorStart);
+                    self.let_body_to_tokens(v, bindings, tokens);
+                    tokens.push(Token::VectorEnd);
+                },
+                ValueType::Number(n) => tokens.push(Token::Number(n.numerator, n.denominator)),
+                ValueType::String(s) => tokens.push(Token::String(s.clone())),
+                ValueType::Boolean(b) => tokens.push(Token::Boolean(*b)),
+                ValueType::Nil => tokens.push(Token::Nil),
+                ValueType::Symbol(s) => tokens.push(Token::Symbol(*s)),
+                ValueType::Error(_) | ValueType::Sequence(_) => tokens.push(Token::Literal(val.clone())),
+            }
+            i += 1;
+        }
+    }
+
+    fn op_call(&mut self) -> Result<(), String> {
+        if let Some(val) = self.stack.pop() {
+            match val.val_type {
+                ValueType::Vector(body) => self.execute_quotation(&body),
+                _ => Err("Type error: CALL requires a vector".to_string()),
+            }
+        } else {
+            Err("Stack underflow".to_string())
+        }
+    }
+
+    // 2番目の値を退避してからクォーテーションを実行し、最後に戻す
+    fn op_dip(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(n1.ge(n2)) });
-                Ok(())
+        let quot = self.stack.pop().unwrap();
+        let saved = self.stack.pop().unwrap();
+        match quot.val_type {
+            ValueType::Vector(body) => {
+                self.push_control(Control::Push(saved))?;
+                self.execute_quotation(&body)
             },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(en.ge(n))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(n.ge(en))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
-            },
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
+            _ => Err("Type error: DIP requires a value and a vector".to_string()),
+        }
+    }
+
+    // 引数にVectorがあれば、クォーテーションを要素ごとに適用する
+    fn op_lift(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let arity_val = self.stack.pop().unwrap();
+        let quot = self.stack.pop().unwrap();
+        match (&quot.val_type, &arity_val.val_type) {
+            (ValueType::Vector(body), ValueType::Number(n)) => {
+                if n.denominator != 1 || n.numerator < 1 {
+                    return Err("LIFT requires a positive integer arity".to_string());
                 }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Boolean(n1.ge(n2))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+                let code = self.compile_quotation(body)?;
+                self.apply_lifted(code, n.numerator as usize, None)
             },
-            _ => {
-                self.stack.push(a);
-                Ok(())
-            }
+            _ => Err("Type error: LIFT requires a vector and a number".to_string()),
         }
     }
-    
-    fn op_eq(&mut self) -> Result<(), String> {
+
+    // 2番目の値を残したままクォーテーションを実行し、最後にその値を再度積む
+    fn op_keep(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        // =演算子はVectorの比較もサポートするが、暗黙の反復も行う
-        match (&a.val_type, &b.val_type) {
-            // スカラー同士（従来通り）
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(n1.eq(n2)) });
-                Ok(())
-            },
-            (ValueType::String(s1), ValueType::String(s2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(s1 == s2) });
-                Ok(())
+        let quot = self.stack.pop().unwrap();
+        let kept = self.stack.last().unwrap().clone();
+        match quot.val_type {
+            ValueType::Vector(body) => {
+                self.push_control(Control::Push(kept))?;
+                self.execute_quotation(&body)
             },
-            (ValueType::Boolean(b1), ValueType::Boolean(b2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(b1 == b2) });
-                Ok(())
+            _ => Err("Type error: KEEP requires a value and a vector".to_string()),
+        }
+    }
+
+    // 条件クォーテーションが残した真偽値を取り出す
+    fn pop_loop_condition(&mut self, word: &str) -> Result<bool, String> {
+        match self.stack.pop() {
+            Some(Value { val_type: ValueType::Boolean(b) }) => Ok(b),
+            Some(_) => Err(format!("Type error: {} condition must leave a boolean", word)),
+            None => Err(format!("Stack underflow in {} condition", word)),
+        }
+    }
+
+    // ループは再帰ではなく継続のフレームとして反復する
+    fn op_times(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let count = self.stack.pop().unwrap();
+        match (&count.val_type, &quot.val_type) {
+            (ValueType::Number(n), ValueType::Vector(body)) => {
+                if n.denominator != 1 || n.numerator < 0 {
+                    return Err("TIMES requires a non-negative integer".to_string());
+                }
+                let body = self.compile_quotation(body)?;
+                self.push_control(Control::Times { body, remaining: n.numerator })
             },
-            (ValueType::Symbol(s1), ValueType::Symbol(s2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(s1 == s2) });
-                Ok(())
+            _ => Err("Type error: TIMES requires a number and a vector".to_string()),
+        }
+    }
+
+    fn op_while(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let body_val = self.stack.pop().unwrap();
+        let cond_val = self.stack.pop().unwrap();
+        match (&cond_val.val_type, &body_val.val_type) {
+            (ValueType::Vector(cond), ValueType::Vector(body)) => {
+                let cond = self.compile_quotation(cond)?;
+                let body = self.compile_quotation(body)?;
+                self.push_control(Control::While { cond, body, stage: LoopStage::Test })
             },
-            (ValueType::Nil, ValueType::Nil) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(true) });
-                Ok(())
+            _ => Err("Type error: WHILE requires two vectors".to_string()),
+        }
+    }
+
+    fn op_until(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let cond_val = self.stack.pop().unwrap();
+        let body_val = self.stack.pop().unwrap();
+        match (&body_val.val_type, &cond_val.val_type) {
+            (ValueType::Vector(body), ValueType::Vector(cond)) => {
+                let body = self.compile_quotation(body)?;
+                let cond = self.compile_quotation(cond)?;
+                self.push_control(Control::Until { body, cond, stage: LoopStage::Body })
             },
-            // Vector全体の比較
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() == v2.len() && v1 == v2 {
-                    self.stack.push(Value { val_type: ValueType::Boolean(true) });
-                } else {
-                    self.stack.push(Value { val_type: ValueType::Boolean(false) });
+            _ => Err("Type error: UNTIL requires two vectors".to_string()),
+        }
+    }
+
+    // startからend-1までのインデックスを積んでから本体を実行
+    fn op_do_range(&mut self) -> Result<(), String> {
+        if self.stack.len() < 3 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let end_val = self.stack.pop().unwrap();
+        let start_val = self.stack.pop().unwrap();
+        match (&start_val.val_type, &end_val.val_type, &quot.val_type) {
+            (ValueType::Number(start), ValueType::Number(end), ValueType::Vector(body)) => {
+                if start.denominator != 1 || end.denominator != 1 {
+                    return Err("DO-RANGE requires integer bounds".to_string());
                 }
-                Ok(())
-            },
-            // 異なる型の場合はfalse
-            _ => {
-                self.stack.push(Value { val_type: ValueType::Boolean(false) });
-                Ok(())
+                let body = self.compile_quotation(body)?;
+                self.push_control(Control::DoRange { body, next: start.numerator, end: end.numerator })
             },
+            _ => Err("Type error: DO-RANGE requires two numbers and a vector".to_string()),
         }
     }
-    
-    fn op_lt(&mut self) -> Result<(), String> {
+
+    // 要素ごとの適用を開始する（累積値があれば先に積み、その下を基準の深さとする）
+    fn start_iteration(
+        &mut self,
+        kind: IterationKind,
+        body: &VectorData,
+        items: Vec<Value>,
+        others: Vec<Value>,
+        acc: Option<Value>,
+    ) -> Result<(), String> {
+        let body = self.compile_quotation(body)?;
+        let base = self.stack.len();
+        self.stack.extend(acc);
+        self.push_control(Control::Iterate(Iteration {
+            kind,
+            body,
+            items,
+            others,
+            index: 0,
+            results: Vec::new(),
+            base,
+        }))
+    }
+
+    fn op_map(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(n1.lt(n2)) });
-                Ok(())
-            },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(en.lt(n))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+        let quot = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match (vec_val.val_type, &quot.val_type) {
+            (ValueType::Vector(v), ValueType::Vector(body)) => {
+                self.start_iteration(IterationKind::Map, body, v.into_vec(), Vec::new(), None)
             },
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(n.lt(en))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+            _ => Err("Type error: MAP requires two vectors".to_string()),
+        }
+    }
+
+    // FILTERは真になった要素を、REJECTは偽になった要素を残す
+    fn op_filter(&mut self, reject: bool) -> Result<(), String> {
+        let (kind, word) = if reject {
+            (IterationKind::Reject, "REJECT")
+        } else {
+            (IterationKind::Filter, "FILTER")
+        };
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match (vec_val.val_type, &quot.val_type) {
+            (ValueType::Vector(v), ValueType::Vector(body)) => {
+                self.start_iteration(kind, body, v.into_vec(), Vec::new(), None)
             },
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
-                if v1.len() != v2.len() {
-                    return Err("Vector length mismatch".to_string());
-                }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Boolean(n1.lt(n2))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+            _ => Err(format!("Type error: {} requires two vectors", word)),
+        }
+    }
+
+    fn op_fold(&mut self) -> Result<(), String> {
+        if self.stack.len() < 3 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let init = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match (vec_val.val_type, &quot.val_type) {
+            (ValueType::Vector(v), ValueType::Vector(body)) => {
+                self.start_iteration(IterationKind::Fold, body, v.into_vec(), Vec::new(), Some(init))
             },
-            _ => {
-                self.stack.push(a);
-                Ok(())
-            }
+            _ => Err("Type error: FOLD requires a vector, an initial value and a vector".to_string()),
         }
     }
-    
-    fn op_le(&mut self) -> Result<(), String> {
+
+    // 先頭要素を初期値とするFOLD
+    fn op_reduce(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let b = self.stack.pop().unwrap();
-        let a = self.stack.pop().unwrap();
-        
-        match (&a.val_type, &b.val_type) {
-            (ValueType::Number(n1), ValueType::Number(n2)) => {
-                self.stack.push(Value { val_type: ValueType::Boolean(n1.le(n2)) });
-                Ok(())
+        let quot = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match (vec_val.val_type, &quot.val_type) {
+            (ValueType::Vector(mut v), ValueType::Vector(body)) => {
+                if v.is_empty() {
+                    return Err("REDUCE of empty vector".to_string());
+                }
+                let first = v.remove(0);
+                self.start_iteration(IterationKind::Reduce, body, v.into_vec(), Vec::new(), Some(first))
             },
-            (ValueType::Vector(v), ValueType::Number(n)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(en.le(n))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+            _ => Err("Type error: REDUCE requires two vectors".to_string()),
+        }
+    }
+
+    fn op_each(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match (vec_val.val_type, &quot.val_type) {
+            (ValueType::Vector(v), ValueType::Vector(body)) => {
+                self.start_iteration(IterationKind::Each, body, v.into_vec(), Vec::new(), None)
             },
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                let result: Vec<Value> = v.iter()
-                    .map(|elem| match &elem.val_type {
-                        ValueType::Number(en) => Value {
-                            val_type: ValueType::Boolean(n.le(en))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+            _ => Err("Type error: EACH requires two vectors".to_string()),
+        }
+    }
+
+    // FOLDの途中経過をすべて集めたベクトルを返す
+    fn op_scan(&mut self) -> Result<(), String> {
+        if self.stack.len() < 3 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let init = self.stack.pop().unwrap();
+        let vec_val = self.stack.pop().unwrap();
+        match (vec_val.val_type, &quot.val_type) {
+            (ValueType::Vector(v), ValueType::Vector(body)) => {
+                self.start_iteration(IterationKind::Scan, body, v.into_vec(), Vec::new(), Some(init))
             },
-            (ValueType::Vector(v1), ValueType::Vector(v2)) => {
+            _ => Err("Type error: SCAN requires a vector, an initial value and a vector".to_string()),
+        }
+    }
+
+    fn op_zip_with(&mut self) -> Result<(), String> {
+        if self.stack.len() < 3 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let vec2_val = self.stack.pop().unwrap();
+        let vec1_val = self.stack.pop().unwrap();
+        match (vec1_val.val_type, vec2_val.val_type, &quot.val_type) {
+            (ValueType::Vector(v1), ValueType::Vector(v2), ValueType::Vector(body)) => {
                 if v1.len() != v2.len() {
                     return Err("Vector length mismatch".to_string());
                 }
-                let result: Vec<Value> = v1.iter().zip(v2.iter())
-                    .map(|(a, b)| match (&a.val_type, &b.val_type) {
-                        (ValueType::Number(n1), ValueType::Number(n2)) => Value {
-                            val_type: ValueType::Boolean(n1.le(n2))
-                        },
-                        _ => Value { val_type: ValueType::Boolean(false) }
-                    })
-                    .collect();
-                self.stack.push(Value { val_type: ValueType::Vector(result) });
-                Ok(())
+                self.start_iteration(IterationKind::ZipWith, body, v1.into_vec(), v2.into_vec(), None)
             },
-            _ => {
-                self.stack.push(a);
-                Ok(())
-            }
-        }
-    }
-    
-    fn op_length(&mut self) -> Result<(), String> {
-        if let Some(val) = self.stack.pop() {
-            match val.val_type {
-                ValueType::Vector(v) => {
-                    self.stack.push(Value { val_type: ValueType::Number(Fraction::new(v.len() as i64, 1)) });
-                    Ok(())
-                },
-                _ => Err("Type error: LENGTH requires a vector".to_string()),
-            }
-        } else {
-            Err("Stack underflow".to_string())
-        }
-    }
-    
-    fn op_head(&mut self) -> Result<(), String> {
-        if let Some(val) = self.stack.pop() {
-            match val.val_type {
-                ValueType::Vector(v) => {
-                    if let Some(first) = v.first() {
-                        self.stack.push(first.clone());
-                        Ok(())
-                    } else {
-                        Err("HEAD of empty vector".to_string())
-                    }
-                },
-                _ => Err("Type error: HEAD requires a vector".to_string()),
-            }
-        } else {
-            Err("Stack underflow".to_string())
+            _ => Err("Type error: ZIP-WITH requires three vectors".to_string()),
         }
     }
-    
-    fn op_tail(&mut self) -> Result<(), String> {
-        if let Some(val) = self.stack.pop() {
-            match val.val_type {
-                ValueType::Vector(v) => {
-                    if v.is_empty() {
-                        Err("TAIL of empty vector".to_string())
-                    } else {
-                        let tail: Vec<Value> = v.into_iter().skip(1).collect();
-                        self.stack.push(Value { val_type: ValueType::Vector(tail) });
-                        Ok(())
-                    }
-                },
-                _ => Err("Type error: TAIL requires a vector".to_string()),
-            }
-        } else {
-            Err("Stack underflow".to_string())
+
+    fn op_range(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let end_val = self.stack.pop().unwrap();
+        let start_val = self.stack.pop().unwrap();
+        let end = match end_val.val_type {
+            ValueType::Number(end) => Some(end),
+            ValueType::Nil => None,
+            _ => return Err("Type error: RANGE requires a number and a number or nil".to_string()),
+        };
+        match start_val.val_type {
+            ValueType::Number(start) => {
+                let seq = Sequence::new(SequenceSource::Range { start, end });
+                self.stack.push(Value { val_type: ValueType::Sequence(Box::new(seq)) });
+                Ok(())
+            },
+            _ => Err("Type error: RANGE requires a number and a number or nil".to_string()),
         }
     }
-    
-    fn op_cons(&mut self) -> Result<(), String> {
+
+    fn op_iterate(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let vec_val = self.stack.pop().unwrap();
-        let elem = self.stack.pop().unwrap();
-        match vec_val.val_type {
-            ValueType::Vector(mut v) => {
-                v.insert(0, elem);
-                self.stack.push(Value { val_type: ValueType::Vector(v) });
+        let quot = self.stack.pop().unwrap();
+        let seed = self.stack.pop().unwrap();
+        match quot.val_type {
+            ValueType::Vector(step) => {
+                let seq = Sequence::new(SequenceSource::Iterate { seed, step });
+                self.stack.push(Value { val_type: ValueType::Sequence(Box::new(seq)) });
                 Ok(())
             },
-            _ => Err("Type error: CONS requires an element and a vector".to_string()),
+            _ => Err("Type error: ITERATE requires a value and a vector".to_string()),
         }
     }
 
-    fn op_append(&mut self) -> Result<(), String> {
+    // シーケンスは上限を記録するだけで、ベクトルはその場で切り詰める
+    fn op_take(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let elem = self.stack.pop().unwrap();
-        let vec_val = self.stack.pop().unwrap();
-        match vec_val.val_type {
+        let count_val = self.stack.pop().unwrap();
+        let target = self.stack.pop().unwrap();
+        let count = match count_val.val_type {
+            ValueType::Number(n) if n.denominator == 1 && n.numerator >= 0 => n.numerator as usize,
+            _ => return Err("TAKE requires a non-negative integer".to_string()),
+        };
+        let val_type = match target.val_type {
+            ValueType::Sequence(mut seq) => {
+                seq.limit = Some(seq.limit.map_or(count, |limit| limit.min(count)));
+                ValueType::Sequence(seq)
+            },
             ValueType::Vector(mut v) => {
-                v.push(elem);
-                self.stack.push(Value { val_type: ValueType::Vector(v) });
+                v.truncate(count);
+                ValueType::Vector(v)
+            },
+            _ => return Err("Type error: TAKE requires a sequence or vector and a number".to_string()),
+        };
+        self.stack.push(Value { val_type });
+        Ok(())
+    }
+
+    fn op_take_while(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let target = self.stack.pop().unwrap();
+        let condition = match quot.val_type {
+            ValueType::Vector(condition) => condition,
+            _ => return Err("Type error: TAKE-WHILE requires a sequence or vector and a vector".to_string()),
+        };
+        match target.val_type {
+            ValueType::Sequence(mut seq) => {
+                seq.conditions.push(condition);
+                self.stack.push(Value { val_type: ValueType::Sequence(seq) });
+            },
+            // ベクトルは遅延させずにその場で計算する
+            ValueType::Vector(v) => {
+                let mut seq = Sequence::new(SequenceSource::Items(v));
+                seq.conditions.push(condition);
+                return self.start_forcing(seq, None);
+            },
+            _ => return Err("Type error: TAKE-WHILE requires a sequence or vector and a vector".to_string()),
+        }
+        Ok(())
+    }
+
+    fn op_force(&mut self) -> Result<(), String> {
+        match self.stack.pop() {
+            Some(Value { val_type: ValueType::Sequence(seq) }) => self.start_forcing(*seq, None),
+            Some(val @ Value { val_type: ValueType::Vector(_) }) => {
+                self.stack.push(val);
                 Ok(())
             },
-            _ => Err("Type error: APPEND requires a vector and an element".to_string()),
+            Some(_) => Err("Type error: FORCE requires a sequence or vector".to_string()),
+            None => Err("Stack underflow".to_string()),
         }
     }
-    
-    fn op_reverse(&mut self) -> Result<(), String> {
+
+    // 任意の値を例外として投げる（エラー値はそのまま再送出）
+    fn op_throw(&mut self) -> Result<(), String> {
         if let Some(val) = self.stack.pop() {
-            match val.val_type {
-                ValueType::Vector(mut v) => {
-                    v.reverse();
-                    self.stack.push(Value { val_type: ValueType::Vector(v) });
-                    Ok(())
-                },
-                _ => Err("Type error: REVERSE requires a vector".to_string()),
-            }
+            let payload = match val.val_type {
+                ValueType::Error(payload) => *payload,
+                _ => val,
+            };
+            let message = error_message(&payload);
+            self.thrown = Some(payload);
+            Err(message)
         } else {
             Err("Stack underflow".to_string())
         }
     }
 
-    fn op_nth(&mut self) -> Result<(), String> {
+    // 本体でエラーが起きたらスタックを実行前に戻し、エラー値を積んでハンドラを実行
+    fn op_try(&mut self) -> Result<(), String> {
         if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
-        let vec_val = self.stack.pop().unwrap();
-        let index_val = self.stack.pop().unwrap();
-        match (&index_val.val_type, &vec_val.val_type) {
-            (ValueType::Number(n), ValueType::Vector(v)) => {
-                if n.denominator != 1 { return Err("NTH requires an integer index".to_string()); }
-                let mut index = n.numerator;
-                let len = v.len() as i64;
-                if index < 0 { index = len + index; }
-                if index < 0 || index >= len { return Err(format!("Index {} out of bounds for vector of length {}", n.numerator, len)); }
-                self.stack.push(v[index as usize].clone());
-                Ok(())
+        let handler_val = self.stack.pop().unwrap();
+        let body_val = self.stack.pop().unwrap();
+        match (&body_val.val_type, &handler_val.val_type) {
+            (ValueType::Vector(body), ValueType::Vector(handler)) => {
+                let body = self.compile_quotation(body)?;
+                let handler = self.compile_quotation(handler)?;
+                self.thrown = None;
+                self.push_frame(Frame::Try { saved_stack: self.stack.clone(), handler })?;
+                self.push_code(body, None)
             },
-            _ => Err("Type error: NTH requires a number and a vector".to_string()),
+            _ => Err("Type error: TRY requires two vectors".to_string()),
         }
     }
-    
-    fn op_uncons(&mut self) -> Result<(), String> {
+
+    fn op_error_message(&mut self) -> Result<(), String> {
         if let Some(val) = self.stack.pop() {
             match val.val_type {
-                ValueType::Vector(v) => {
-                    if v.is_empty() { return Err("UNCONS of empty vector".to_string()); }
-                    let mut v_mut = v;
-                    let head = v_mut.remove(0);
-                    self.stack.push(head);
-                    self.stack.push(Value { val_type: ValueType::Vector(v_mut) });
+                ValueType::Error(payload) => {
+                    self.stack.push(Value { val_type: ValueType::String(error_message(&payload)) });
                     Ok(())
                 },
-                _ => Err("Type error: UNCONS requires a vector".to_string()),
+                _ => Err("Type error: ERROR-MESSAGE requires an error".to_string()),
             }
         } else {
             Err("Stack underflow".to_string())
         }
     }
-        
-    fn op_empty(&mut self) -> Result<(), String> {
+
+    fn op_error_value(&mut self) -> Result<(), String> {
         if let Some(val) = self.stack.pop() {
             match val.val_type {
-                ValueType::Vector(v) => {
-                    self.stack.push(Value { val_type: ValueType::Boolean(v.is_empty()) });
+                ValueType::Error(payload) => {
+                    self.stack.push(*payload);
                     Ok(())
                 },
-                _ => Err("Type error: EMPTY? requires a vector".to_string()),
+                _ => Err("Type error: ERROR-VALUE requires an error".to_string()),
             }
         } else {
             Err("Stack underflow".to_string())
         }
     }
-    
-    // IFワードに暗黙の反復を追加
-    fn op_if(&mut self) -> Result<(), String> {
-        if self.stack.len() < 3 {
-            return Err("Stack underflow for IF".to_string());
-        }
-        
-        let else_branch = self.stack.pop().unwrap();
-        let then_branch = self.stack.pop().unwrap();
-        let condition = self.stack.pop().unwrap();
-        
-        web_sys::console::log_1(&format!("op_if: condition={:?}, then={:?}, else={:?}", 
-                                         condition, then_branch, else_branch).into());
-        
-        match (&condition.val_type, &then_branch.val_type, &else_branch.val_type) {
-            // 通常のIF（スカラーの真偽値）
-            (ValueType::Boolean(cond), ValueType::Vector(then_vec), ValueType::Vector(else_vec)) => {
-                let vec_to_execute = if *cond { then_vec } else { else_vec };
-                let (tokens, _) = self.body_vector_to_tokens(vec_to_execute)?;
-                self.execute_tokens_with_context(&tokens)?;
-                Ok(())
-            },
-            // Vectorの真偽値に対する暗黙の反復
-            (ValueType::Vector(cond_vec), ValueType::Vector(then_vec), ValueType::Vector(else_vec)) => {
-                // 各条件に対してIFを実行
-                for cond_val in cond_vec {
-                    match &cond_val.val_type {
-                        ValueType::Boolean(cond) => {
-                            let vec_to_execute = if *cond { then_vec } else { else_vec };
-                            let (tokens, _) = self.body_vector_to_tokens(vec_to_execute)?;
-                            self.execute_tokens_with_context(&tokens)?;
-                        },
-                        _ => {
-                            // 真偽値でない要素はスキップ
-                        }
-                    }
-                }
-                Ok(())
-            },
-            _ => Err("Type error: IF requires a boolean (or vector of booleans) and two vectors".to_string()),
-        }
-    }
 
     fn op_not(&mut self) -> Result<(), String> {
         if let Some(val) = self.stack.pop() {
@@ -1245,19 +2842,23 @@ impl Interpreter {
                     self.stack.push(Value { val_type: ValueType::Boolean(!b) });
                     Ok(())
                 },
-                // Vectorに対してもNOTを適用（暗黙の反復）
-                ValueType::Vector(v) => {
-                    let result: Vec<Value> = v.iter()
-                        .map(|elem| match &elem.val_type {
-                            ValueType::Boolean(b) => Value {
-                                val_type: ValueType::Boolean(!b)
-                            },
-                            _ => elem.clone()
-                        })
-                        .collect();
-                    self.stack.push(Value { val_type: ValueType::Vector(result) });
+                // Vectorに対してもNOTを適用（暗黙の反復、ネストしたVectorにも再帰的に適用）
+                ValueType::Vector(_) => {
+                    fn negate(val: &Value, strict: bool) -> Result<Value, String> {
+                        match &val.val_type {
+                            ValueType::Boolean(b) => Ok(Value { val_type: ValueType::Boolean(!b) }),
+                            ValueType::Vector(v) => Ok(Value {
+                                val_type: ValueType::Vector(v.iter().map(|elem| negate(elem, strict)).collect::<Result<_, _>>()?),
+                            }),
+                            _ if strict => Err(format!("Type error: NOT cannot be applied to {}", val.val_type.type_name())),
+                            _ => Ok(val.clone()),
+                        }
+                    }
+                    let result = negate(&val, self.strict_mode)?;
+                    self.stack.push(result);
                     Ok(())
                 },
+                _ if self.strict_mode => Err(format!("Type error: NOT cannot be applied to {}", val.val_type.type_name())),
                 _ => Err("Type error: NOT requires a boolean or vector of booleans".to_string()),
             }
         } else {
@@ -1265,6 +2866,71 @@ impl Interpreter {
         }
     }
     
+    // 2項の論理演算（NOTと同じくVectorには要素ごとに適用）
+    fn op_logic(&mut self, word: &str, op: fn(bool, bool) -> bool) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let b = self.stack.pop().unwrap();
+        let a = self.stack.pop().unwrap();
+        let result = match (&a.val_type, &b.val_type) {
+            (ValueType::Boolean(p), ValueType::Boolean(q)) => Value { val_type: ValueType::Boolean(op(*p, *q)) },
+            (ValueType::Vector(_), ValueType::Boolean(_) | ValueType::Vector(_))
+            | (ValueType::Boolean(_), ValueType::Vector(_)) => {
+                // 緩いモードでは真偽値でない要素はそのまま残す
+                let strict = self.strict_mode;
+                Self::broadcast(&a, &b, self.broadcast_policy, &|x: &Value, y: &Value| match (&x.val_type, &y.val_type) {
+                    (ValueType::Boolean(p), ValueType::Boolean(q)) => Ok(Some(Value { val_type: ValueType::Boolean(op(*p, *q)) })),
+                    _ if strict => Err(type_mismatch(word, x, y)),
+                    _ => Ok(None),
+                })?.unwrap_or_else(|| a.clone())
+            },
+            _ if self.strict_mode => return Err(type_mismatch(word, &a, &b)),
+            _ => return Err(format!("Type error: {} requires booleans or vectors of booleans", word)),
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    // 真偽値のベクトルをまとめて1つの真偽値にする（スカラーはそのまま判定）
+    fn op_quantifier(&mut self, word: &str) -> Result<(), String> {
+        let val = match self.stack.pop() {
+            Some(val) => val,
+            None => return Err("Stack underflow".to_string()),
+        };
+        let flags: Vec<bool> = match &val.val_type {
+            ValueType::Boolean(b) => vec![*b],
+            ValueType::Vector(v) => v.iter()
+                .map(|elem| match elem.val_type {
+                    ValueType::Boolean(b) => Ok(b),
+                    _ => Err(format!("Type error: {} requires a vector of booleans", word)),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err(format!("Type error: {} requires a boolean or vector of booleans", word)),
+        };
+        let result = match word {
+            "ALL?" => flags.iter().all(|b| *b),
+            "ANY?" => flags.iter().any(|b| *b),
+            _ => !flags.iter().any(|b| *b),
+        };
+        self.stack.push(Value { val_type: ValueType::Boolean(result) });
+        Ok(())
+    }
+
+    // 条件がshort_circuitと等しければクォーテーションを実行せずにその値を返す
+    // （それ以外はクォーテーションの結果が全体の結果になる）
+    fn op_short_circuit(&mut self, word: &str, short_circuit: bool) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let quot = self.stack.pop().unwrap();
+        let cond = self.stack.pop().unwrap();
+        match (&cond.val_type, &quot.val_type) {
+            (ValueType::Boolean(b), ValueType::Vector(_)) if *b == short_circuit => {
+                self.stack.push(cond);
+                Ok(())
+            },
+            (ValueType::Boolean(_), ValueType::Vector(body)) => self.execute_quotation(body),
+            _ => Err(format!("Type error: {} requires a boolean and a vector", word)),
+        }
+    }
+
     fn op_del(&mut self) -> Result<(), String> {
         if let Some(val) = self.stack.pop() {
             match val.val_type {
@@ -1278,6 +2944,56 @@ impl Interpreter {
         }
     }
     
+    fn op_rename(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let new_val = self.stack.pop().unwrap();
+        let old_val = self.stack.pop().unwrap();
+        match (&old_val.val_type, &new_val.val_type) {
+            (ValueType::String(old_name), ValueType::String(new_name)) => {
+                self.rename_word(&old_name.to_uppercase(), &new_name.to_uppercase())
+            },
+            _ => Err("Type error: RENAME requires two strings".to_string()),
+        }
+    }
+    
+    // カスタムワードを要素ごとのワードにする（引数にVectorがあれば要素ごとに適用）
+    fn op_elementwise(&mut self) -> Result<(), String> {
+        if self.stack.len() < 2 { return Err("Stack underflow".to_string()); }
+        let arity_val = self.stack.pop().unwrap();
+        let name_val = self.stack.pop().unwrap();
+        let (name, arity) = match (&name_val.val_type, &arity_val.val_type) {
+            (ValueType::String(name), ValueType::Number(n)) => (Symbol::intern(&name.to_uppercase()), n),
+            _ => return Err("Type error: ELEMENTWISE requires a string and a number".to_string()),
+        };
+        if arity.denominator != 1 || arity.numerator < 1 {
+            return Err("ELEMENTWISE requires a positive integer arity".to_string());
+        }
+        match self.dictionary.get(&name) {
+            Some(def) if def.is_builtin => return Err(format!("Cannot make builtin word elementwise: {}", name)),
+            Some(_) => {},
+            None => return Err(format!("Word not found: {}", name)),
+        }
+        // 使われているワードの意味はDEFと同じく変えられない
+        // （自身の再帰呼び出しは一緒にコンパイルし直すので除く）
+        if let Some(dependents) = self.dependencies.get(&name) {
+            let dependent_list: Vec<String> = dependents.iter()
+                .filter(|dep| **dep != name)
+                .map(|dep| dep.to_string())
+                .collect();
+            if !dependent_list.is_empty() {
+                return Err(format!(
+                    "Cannot make '{}' elementwise because it is used by: {}",
+                    name,
+                    dependent_list.join(", ")
+                ));
+            }
+        }
+        if let Some(def) = self.dictionary.get_mut(&name) {
+            def.elementwise = Some(arity.numerator as usize);
+        }
+        self.recompile(name)
+    }
+
     // 出力ワードの実装（修正版）
     fn op_dot(&mut self) -> Result<(), String> {
         if let Some(val) = self.stack.pop() {
@@ -1356,6 +3072,36 @@ impl Interpreter {
         }
     }
     
+    // 実行統計を [ 名前 値 ] の組のベクトルとして積む
+    // （builtinsは [ ワード 呼び出し回数 マイクロ秒 ] の組で、プロファイル中のみ）
+    fn op_stats(&mut self) -> Result<(), String> {
+        let stats = self.get_stats();
+        let number = |n: u64| Value { val_type: ValueType::Number(Fraction::new(n as i64, 1)) };
+        let string = |s: &str| Value { val_type: ValueType::String(s.to_string()) };
+        let pair = |name: &str, value: Value| Value {
+            val_type: ValueType::Vector(vec![string(name), value].into()),
+        };
+        let builtins: Vec<Value> = stats.builtins_by_time().into_iter()
+            .map(|(name, builtin)| Value {
+                val_type: ValueType::Vector(vec![
+                    string(name.as_str()),
+                    number(builtin.calls),
+                    number((builtin.time_ms * 1000.0).round() as u64),
+                ].into()),
+            })
+            .collect();
+        let entries = vec![
+            pair("instructions", number(stats.instructions)),
+            pair("word-calls", number(stats.word_calls)),
+            pair("max-stack-depth", number(stats.max_stack_depth as u64)),
+            pair("max-call-depth", number(stats.max_call_depth as u64)),
+            pair("vector-elements", number(stats.vector_elements)),
+            pair("builtins", Value { val_type: ValueType::Vector(builtins.into()) }),
+        ];
+        self.stack.push(Value { val_type: ValueType::Vector(entries.into()) });
+        Ok(())
+    }
+
     pub fn get_stack(&self) -> &Stack { &self.stack }
     
     pub fn get_register(&self) -> &Register { &self.register }
@@ -1364,7 +3110,7 @@ impl Interpreter {
         let mut words: Vec<String> = self.dictionary
             .iter()
             .filter(|(_, def)| !def.is_builtin)
-            .map(|(name, _)| name.clone())
+            .map(|(name, _)| name.to_string())
             .collect();
         words.sort();
         words
@@ -1374,23 +3120,56 @@ impl Interpreter {
        let mut words: Vec<(String, Option<String>)> = self.dictionary
            .iter()
            .filter(|(_, def)| !def.is_builtin)
-           .map(|(name, def)| (name.clone(), def.description.clone()))
+           .map(|(name, def)| (name.to_string(), def.description.clone()))
            .collect();
        words.sort_by(|a, b| a.0.cmp(&b.0));
        words
    }
    
+   // スタック・レジスタ・カスタムワードのおおよそのメモリ使用量
+   pub fn get_memory_usage(&self) -> MemoryReport {
+       let mut dictionary: Vec<(String, memory::MemoryUsage)> = self.dictionary
+           .iter()
+           .filter(|(_, def)| !def.is_builtin)
+           .map(|(name, def)| {
+               let mut usage = memory::tokens_usage(&def.tokens);
+               usage.bytes += def.description.as_ref().map_or(0, |d| d.capacity());
+               usage.bytes += memory::code_usage(&def.code, &|items| self.literal_code(items));
+               (name.to_string(), usage)
+           })
+           .collect();
+       dictionary.sort_by(|a, b| a.0.cmp(&b.0));
+       MemoryReport {
+           stack: self.stack.iter().map(memory::value_usage).collect(),
+           register: self.register.as_ref().map(memory::value_usage),
+           dictionary,
+       }
+   }
+
    pub fn get_custom_words_info(&self) -> Vec<(String, Option<String>, bool)> {
        let mut words: Vec<(String, Option<String>, bool)> = self.dictionary
            .iter()
            .filter(|(_, def)| !def.is_builtin)
            .map(|(name, def)| {
                let is_protected = self.dependencies.get(name)
-                   .map_or(false, |deps| !deps.is_empty());
-               (name.clone(), def.description.clone(), is_protected)
+                   .is_some_and(|deps| !deps.is_empty());
+               (name.to_string(), def.description.clone(), is_protected)
            })
            .collect();
        words.sort_by(|a, b| a.0.cmp(&b.0));
        words
    }
 }
+
+// 演算子に渡された値の型が合わないときのエラーメッセージ
+fn type_mismatch(word: &str, a: &Value, b: &Value) -> String {
+    format!("Type error: {} cannot be applied to {} and {}", word, a.val_type.type_name(), b.val_type.type_name())
+}
+
+// エラー値のメッセージ（文字列はそのまま、それ以外は表示形式）
+fn error_message(payload: &Value) -> String {
+    match &payload.val_type {
+        ValueType::String(s) => s.clone(),
+        _ => payload.to_string(),
+    }
+}
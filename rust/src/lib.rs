@@ -1,12 +1,18 @@
 use wasm_bindgen::prelude::*;
 
 mod types;
+mod symbol;
+mod stats;
+mod memory;
+pub mod trace;
 mod tokenizer;
 mod interpreter;
 mod builtins;
 
 use types::*;
 use interpreter::*;
+use trace::{LogLevel, LogRecord, LogSink};
+use memory::MemoryUsage;
 
 #[wasm_bindgen]
 pub struct AjisaiInterpreter {
@@ -17,9 +23,10 @@ pub struct AjisaiInterpreter {
 impl AjisaiInterpreter {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
-        AjisaiInterpreter {
-            interpreter: Interpreter::new(),
-        }
+        let mut interpreter = Interpreter::new();
+        // GUIではエラーで積み上げたスタックを失わないようにする
+        interpreter.set_atomic_mode(true);
+        AjisaiInterpreter { interpreter }
     }
 
     #[wasm_bindgen]
@@ -39,6 +46,53 @@ impl AjisaiInterpreter {
         }
     }
 
+    // 実行を開始する（続きはrunで少しずつ進める）
+    #[wasm_bindgen]
+    pub fn start(&mut self, code: &str) -> Result<String, String> {
+        match self.interpreter.start(code) {
+            Ok(()) => Ok("OK".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // 最大budgetステップだけ実行を進める（requestAnimationFrameから繰り返し呼び出す）
+    #[wasm_bindgen]
+    pub fn run(&mut self, budget: u32) -> JsValue {
+        let obj = js_sys::Object::new();
+        let status = match self.interpreter.run(budget as u64) {
+            Ok(RunStatus::Suspended) => "Suspended",
+            Ok(RunStatus::Done) => "Done",
+            Err(e) => {
+                js_sys::Reflect::set(&obj, &"message".into(), &e.into()).unwrap();
+                "Error"
+            }
+        };
+        js_sys::Reflect::set(&obj, &"status".into(), &status.into()).unwrap();
+
+        // 出力を取得
+        let output = self.interpreter.get_output();
+        js_sys::Reflect::set(&obj, &"output".into(), &output.into()).unwrap();
+
+        obj.into()
+    }
+
+    #[wasm_bindgen]
+    pub fn is_running(&self) -> bool {
+        self.interpreter.is_running()
+    }
+
+    // 実行中のプログラムを中断する（次のrunがInterruptedエラーを返す）
+    #[wasm_bindgen]
+    pub fn interrupt(&self) {
+        self.interpreter.interrupt();
+    }
+
+    // 実行中の継続を破棄する（ステップ実行をやめて別のコードを実行するときなど）
+    #[wasm_bindgen]
+    pub fn cancel(&mut self) {
+        self.interpreter.cancel();
+    }
+
     #[wasm_bindgen]
     pub fn init_step(&mut self, code: &str) -> Result<String, String> {
         match self.interpreter.init_step_execution(code) {
@@ -74,7 +128,7 @@ impl AjisaiInterpreter {
         let stack_values: Vec<JsValue> = self.interpreter
             .get_stack()
             .iter()
-            .map(|v| value_to_js(v))
+            .map(value_to_js)
             .collect();
         
         let arr = js_sys::Array::new();
@@ -128,23 +182,200 @@ impl AjisaiInterpreter {
         arr.into()
     }
 
+    #[wasm_bindgen]
+    pub fn rename_word(&mut self, old_name: &str, new_name: &str) -> Result<String, String> {
+        match self.interpreter.rename_word(&old_name.to_uppercase(), &new_name.to_uppercase()) {
+            Ok(()) => Ok("OK".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn set_max_call_depth(&mut self, depth: u32) {
+        self.interpreter.set_max_call_depth(depth as usize);
+    }
+
+    // undefinedを渡すと、executeは既定の上限・start/runは無制限
+    #[wasm_bindgen]
+    pub fn set_max_steps(&mut self, steps: Option<u32>) {
+        self.interpreter.set_max_steps(steps.map(|s| s as u64));
+    }
+
+    #[wasm_bindgen]
+    pub fn set_atomic_mode(&mut self, enabled: bool) {
+        self.interpreter.set_atomic_mode(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_atomic_mode(&self) -> bool {
+        self.interpreter.is_atomic_mode()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_strict_mode(&mut self, enabled: bool) {
+        self.interpreter.set_strict_mode(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_strict_mode(&self) -> bool {
+        self.interpreter.is_strict_mode()
+    }
+
+    // ERROR / CYCLE / PAD / TRUNCATE
+    #[wasm_bindgen]
+    pub fn set_broadcast_policy(&mut self, policy: &str) -> Result<String, String> {
+        match BroadcastPolicy::from_name(policy) {
+            Some(policy) => {
+                self.interpreter.set_broadcast_policy(policy);
+                Ok("OK".to_string())
+            }
+            None => Err(format!("Unknown broadcast policy: {}", policy)),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn get_broadcast_policy(&self) -> String {
+        self.interpreter.get_broadcast_policy().name().to_string()
+    }
+
+    // 組み込みワードごとの回数と時間も計測する（少し遅くなる）
+    #[wasm_bindgen]
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.interpreter.set_profiling(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_profiling(&self) -> bool {
+        self.interpreter.is_profiling()
+    }
+
+    // 直近に開始したプログラムの実行統計
+    #[wasm_bindgen]
+    pub fn get_stats(&self) -> JsValue {
+        let stats = self.interpreter.get_stats();
+        let obj = js_sys::Object::new();
+        js_sys::Reflect::set(&obj, &"instructions".into(), &JsValue::from_f64(stats.instructions as f64)).unwrap();
+        js_sys::Reflect::set(&obj, &"wordCalls".into(), &JsValue::from_f64(stats.word_calls as f64)).unwrap();
+        js_sys::Reflect::set(&obj, &"maxStackDepth".into(), &JsValue::from_f64(stats.max_stack_depth as f64)).unwrap();
+        js_sys::Reflect::set(&obj, &"maxCallDepth".into(), &JsValue::from_f64(stats.max_call_depth as f64)).unwrap();
+        js_sys::Reflect::set(&obj, &"vectorElements".into(), &JsValue::from_f64(stats.vector_elements as f64)).unwrap();
+
+        let builtins = js_sys::Array::new();
+        for (name, builtin) in stats.builtins_by_time() {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &"name".into(), &name.as_str().into()).unwrap();
+            js_sys::Reflect::set(&entry, &"calls".into(), &JsValue::from_f64(builtin.calls as f64)).unwrap();
+            js_sys::Reflect::set(&entry, &"timeMs".into(), &JsValue::from_f64(builtin.time_ms)).unwrap();
+            builtins.push(&entry);
+        }
+        js_sys::Reflect::set(&obj, &"builtins".into(), &builtins).unwrap();
+
+        obj.into()
+    }
+
+    // スタック・レジスタ・辞書のおおよそのメモリ使用量（バイト数と値の数）
+    #[wasm_bindgen]
+    pub fn get_memory_usage(&self) -> JsValue {
+        let report = self.interpreter.get_memory_usage();
+        let obj = js_sys::Object::new();
+
+        let stack = usage_to_js(report.stack_total());
+        let items = js_sys::Array::new();
+        for usage in &report.stack {
+            items.push(&usage_to_js(*usage));
+        }
+        js_sys::Reflect::set(&stack, &"items".into(), &items).unwrap();
+        js_sys::Reflect::set(&obj, &"stack".into(), &stack).unwrap();
+
+        let register = report.register.map(usage_to_js).unwrap_or(JsValue::NULL);
+        js_sys::Reflect::set(&obj, &"register".into(), &register).unwrap();
+
+        let dictionary = usage_to_js(report.dictionary_total());
+        let words = js_sys::Array::new();
+        for (name, usage) in &report.dictionary {
+            let word = usage_to_js(*usage);
+            js_sys::Reflect::set(&word, &"name".into(), &JsValue::from_str(name)).unwrap();
+            words.push(&word);
+        }
+        js_sys::Reflect::set(&dictionary, &"words".into(), &words).unwrap();
+        js_sys::Reflect::set(&obj, &"dictionary".into(), &dictionary).unwrap();
+
+        js_sys::Reflect::set(&obj, &"total".into(), &usage_to_js(report.total())).unwrap();
+
+        obj.into()
+    }
+
     #[wasm_bindgen]
     pub fn reset(&mut self) {
+        let atomic_mode = self.interpreter.is_atomic_mode();
+        let strict_mode = self.interpreter.is_strict_mode();
+        let broadcast_policy = self.interpreter.get_broadcast_policy();
+        let profiling = self.interpreter.is_profiling();
         self.interpreter = Interpreter::new();
+        self.interpreter.set_atomic_mode(atomic_mode);
+        self.interpreter.set_strict_mode(strict_mode);
+        self.interpreter.set_broadcast_policy(broadcast_policy);
+        self.interpreter.set_profiling(profiling);
     }
 }
 
+// JSのコールバックにログを渡す（引数は level, target, message）
+struct JsLogSink {
+    callback: js_sys::Function,
+}
+
+impl LogSink for JsLogSink {
+    fn log(&mut self, record: &LogRecord) {
+        let _ = self.callback.call3(
+            &JsValue::NULL,
+            &record.level.name().into(),
+            &record.target.into(),
+            &record.message.as_str().into(),
+        );
+    }
+}
+
+fn parse_log_level(level: &str) -> Result<LogLevel, String> {
+    LogLevel::from_name(level)
+        .ok_or_else(|| format!("Unknown log level: {} (use ERROR, WARN, INFO, DEBUG or TRACE)", level))
+}
+
+// ログの出力先はすべてのインタプリタで共有する（既定では出力しない）
+#[wasm_bindgen]
+pub fn set_log_sink(callback: js_sys::Function, level: &str) -> Result<String, String> {
+    let level = parse_log_level(level)?;
+    trace::set_sink(Box::new(JsLogSink { callback }), level);
+    Ok("OK".to_string())
+}
+
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<String, String> {
+    trace::set_level(parse_log_level(level)?);
+    Ok("OK".to_string())
+}
+
+#[wasm_bindgen]
+pub fn clear_log_sink() {
+    trace::clear_sink();
+}
+
+impl Default for AjisaiInterpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn usage_to_js(usage: MemoryUsage) -> JsValue {
+    let obj = js_sys::Object::new();
+    js_sys::Reflect::set(&obj, &"bytes".into(), &JsValue::from_f64(usage.bytes as f64)).unwrap();
+    js_sys::Reflect::set(&obj, &"elements".into(), &JsValue::from_f64(usage.elements as f64)).unwrap();
+    obj.into()
+}
+
 fn value_to_js(value: &Value) -> JsValue {
     let obj = js_sys::Object::new();
     
-    let type_str = match &value.val_type {
-        ValueType::Number(_) => "number",
-        ValueType::String(_) => "string",
-        ValueType::Boolean(_) => "boolean",
-        ValueType::Symbol(_) => "symbol",
-        ValueType::Vector(_) => "vector",
-        ValueType::Nil => "nil",
-    };
+    let type_str = value.val_type.type_name();
     
     js_sys::Reflect::set(&obj, &"type".into(), &type_str.into()).unwrap();
     
@@ -165,7 +396,7 @@ fn value_to_js(value: &Value) -> JsValue {
         },
         ValueType::String(s) => JsValue::from_str(s),
         ValueType::Boolean(b) => JsValue::from_bool(*b),
-        ValueType::Symbol(s) => JsValue::from_str(s),
+        ValueType::Symbol(s) => JsValue::from_str(s.as_str()),
         ValueType::Vector(v) => {
             let arr = js_sys::Array::new();
             for item in v.iter() {
@@ -174,6 +405,9 @@ fn value_to_js(value: &Value) -> JsValue {
             arr.into()
         },
         ValueType::Nil => JsValue::NULL,
+        ValueType::Error(payload) => value_to_js(payload),
+        // 遅延シーケンスは要素を計算せず、表示用の文字列で返す
+        ValueType::Sequence(seq) => JsValue::from_str(&seq.to_string()),
     };
     
     js_sys::Reflect::set(&obj, &"value".into(), &val).unwrap();
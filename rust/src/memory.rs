@@ -0,0 +1,152 @@
+use std::mem::size_of;
+use crate::tokenizer::Token;
+use crate::types::*;
+use crate::interpreter::{CompiledLiteral, Instruction, Quotation};
+
+// おおよそのメモリ使用量（共有しているVectorは参照ごとに数える）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub bytes: usize,
+    // ネストしたVectorの中身も含めた値（トークン）の数
+    pub elements: usize,
+}
+
+impl MemoryUsage {
+    fn add(&mut self, other: MemoryUsage) {
+        self.bytes += other.bytes;
+        self.elements += other.elements;
+    }
+}
+
+impl std::iter::Sum for MemoryUsage {
+    fn sum<I: Iterator<Item = MemoryUsage>>(iter: I) -> Self {
+        let mut total = MemoryUsage::default();
+        for usage in iter {
+            total.add(usage);
+        }
+        total
+    }
+}
+
+// スタック・レジスタ・辞書のメモリ使用量
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryReport {
+    // スタックの各要素（底から順）
+    pub stack: Vec<MemoryUsage>,
+    pub register: Option<MemoryUsage>,
+    // カスタムワードごとのトークン列・説明・コンパイル済みの本体（名前順）
+    pub dictionary: Vec<(String, MemoryUsage)>,
+}
+
+impl MemoryReport {
+    pub fn stack_total(&self) -> MemoryUsage {
+        self.stack.iter().copied().sum()
+    }
+
+    pub fn dictionary_total(&self) -> MemoryUsage {
+        self.dictionary.iter().map(|(_, usage)| *usage).sum()
+    }
+
+    pub fn total(&self) -> MemoryUsage {
+        let mut total = self.stack_total();
+        total.add(self.register.unwrap_or_default());
+        total.add(self.dictionary_total());
+        total
+    }
+}
+
+pub fn value_usage(value: &Value) -> MemoryUsage {
+    let mut usage = value_heap_usage(value);
+    usage.bytes += size_of::<Value>();
+    usage
+}
+
+pub fn tokens_usage(tokens: &[Token]) -> MemoryUsage {
+    let mut usage = MemoryUsage { bytes: std::mem::size_of_val(tokens), elements: 0 };
+    for token in tokens {
+        match token {
+            Token::String(s) | Token::Description(s) => usage.add(MemoryUsage { bytes: s.capacity(), elements: 1 }),
+            // 値そのものはトークンの中に収まっているので、その外側の分だけ加える
+            Token::Literal(value) => usage.add(value_heap_usage(value)),
+            // Vectorは値と同じく開き括弧を1つの値として数える
+            Token::VectorEnd => {},
+            _ => usage.elements += 1,
+        }
+    }
+    usage
+}
+
+// コンパイル済みの命令列のバイト数（ほかのワードの本体は共有しているので数えない）
+// （リテラルのコンパイル結果はliteral_codeで引く）
+pub fn code_usage(code: &[Instruction], literal_code: &dyn Fn(&VectorData) -> Option<Quotation>) -> usize {
+    instructions_usage(code, literal_code, true)
+}
+
+// 命令列のバイト数（リテラルの要素から作った命令列では、積む値は要素と共有しているので数えない）
+fn instructions_usage(code: &[Instruction], literal_code: &dyn Fn(&VectorData) -> Option<Quotation>, count_values: bool) -> usize {
+    let mut bytes = size_of::<usize>() * 2 + size_of::<Vec<Instruction>>() + std::mem::size_of_val(code);
+    for instruction in code {
+        match instruction {
+            Instruction::Push(value) => {
+                if count_values {
+                    bytes += value_heap_usage(value).bytes;
+                }
+                bytes += literal_code_usage(value, literal_code);
+            },
+            Instruction::Describe(text) => bytes += text.capacity(),
+            Instruction::Lift { code, .. } => bytes += code_usage(code, literal_code),
+            _ => {},
+        }
+    }
+    bytes
+}
+
+// ベクタのリテラルに付けたコンパイル結果のバイト数
+// （ネストしたリテラルはコンパイル結果の中で積む値として一度だけたどる）
+fn literal_code_usage(value: &Value, literal_code: &dyn Fn(&VectorData) -> Option<Quotation>) -> usize {
+    match &value.val_type {
+        ValueType::Vector(items) => literal_code(items).map_or(0, |code| {
+            size_of::<CompiledLiteral>() + instructions_usage(&code, literal_code, false)
+        }),
+        _ => 0,
+    }
+}
+
+// 値の外側にある（ヒープ上の）使用量と、中に含まれる値の数（自身を含む）
+fn value_heap_usage(value: &Value) -> MemoryUsage {
+    let mut usage = MemoryUsage { bytes: 0, elements: 1 };
+    match &value.val_type {
+        ValueType::String(s) => usage.bytes += s.capacity(),
+        ValueType::Vector(items) => usage.add(vector_heap_usage(items)),
+        ValueType::Error(payload) => usage.add(value_usage(payload)),
+        ValueType::Sequence(seq) => {
+            usage.bytes += size_of::<Sequence>();
+            for condition in &seq.conditions {
+                usage.bytes += size_of::<VectorData>();
+                usage.add(vector_heap_usage(condition));
+            }
+            match &seq.source {
+                SequenceSource::Range { .. } => {},
+                SequenceSource::Iterate { seed, step } => {
+                    usage.add(value_heap_usage(seed));
+                    usage.add(vector_heap_usage(step));
+                },
+                SequenceSource::Items(items) => usage.add(vector_heap_usage(items)),
+            }
+        },
+        ValueType::Number(_) | ValueType::Boolean(_) | ValueType::Symbol(_) | ValueType::Nil => {},
+    }
+    usage
+}
+
+// 参照カウントと配列の領域、要素が持つヒープ
+fn vector_heap_usage(items: &VectorData) -> MemoryUsage {
+    let mut usage = MemoryUsage {
+        bytes: size_of::<usize>() * 2 + size_of::<Vec<Value>>() + size_of::<Value>() * items.capacity(),
+        elements: 0,
+    };
+    for item in items.iter() {
+        usage.add(value_heap_usage(item));
+    }
+    usage
+}
@@ -0,0 +1,77 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use crate::symbol::Symbol;
+
+// 実行統計（startのたびに0から数え直す）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionStats {
+    // 実行した命令（トークン）の数
+    pub instructions: u64,
+    // カスタムワードの呼び出し回数
+    pub word_calls: u64,
+    pub max_stack_depth: usize,
+    // 同時に実行中だったカスタムワードの最大数（制御構造やクォーテーションのフレームは数えない）
+    pub max_call_depth: usize,
+    // 実行中に新しく作られたVectorの要素数（共有を解いて複製したときも含む）
+    pub vector_elements: u64,
+    // 組み込みワードごとの呼び出し回数と所要時間（プロファイル中のみ）
+    pub builtins: HashMap<Symbol, BuiltinStats>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BuiltinStats {
+    pub calls: u64,
+    // 所要時間（ミリ秒）
+    pub time_ms: f64,
+}
+
+impl ExecutionStats {
+    // 所要時間の長い順
+    pub fn builtins_by_time(&self) -> Vec<(Symbol, BuiltinStats)> {
+        let mut builtins: Vec<(Symbol, BuiltinStats)> = self.builtins.iter().map(|(name, stats)| (*name, *stats)).collect();
+        builtins.sort_by(|a, b| b.1.time_ms.total_cmp(&a.1.time_ms).then_with(|| a.0.as_str().cmp(b.0.as_str())));
+        builtins
+    }
+}
+
+thread_local! {
+    static VECTOR_ELEMENTS: Cell<u64> = const { Cell::new(0) };
+}
+
+// Vectorの要素を確保したときに呼ぶ
+pub(crate) fn count_vector_elements(count: usize) {
+    VECTOR_ELEMENTS.with(|total| total.set(total.get() + count as u64));
+}
+
+// これまでに確保した要素数の累計（差分を取って使う）
+pub(crate) fn vector_elements_allocated() -> u64 {
+    VECTOR_ELEMENTS.with(|total| total.get())
+}
+
+// 経過時間の計測用の時刻（ミリ秒）
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_ms() -> f64 {
+    use wasm_bindgen::JsCast;
+    thread_local! {
+        static NOW: Option<(wasm_bindgen::JsValue, js_sys::Function)> = {
+            let performance = js_sys::Reflect::get(&js_sys::global(), &"performance".into()).ok();
+            performance.and_then(|performance| {
+                let now = js_sys::Reflect::get(&performance, &"now".into()).ok()?;
+                Some((performance, now.dyn_into().ok()?))
+            })
+        };
+    }
+    NOW.with(|now| match now {
+        Some((performance, now)) => now.call0(performance).ok().and_then(|t| t.as_f64()).unwrap_or(0.0),
+        // performance.nowがない環境ではミリ秒単位の時計を使う
+        None => js_sys::Date::now(),
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
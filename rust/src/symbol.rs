@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+// インターンしたシンボル（比較・ハッシュは番号で行い、名前は表から引く）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+// 名前は実行中ずっと使うため解放しない
+#[derive(Default)]
+struct Interner {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, Symbol>,
+    // ソース上の綴りから大文字に正規化したシンボルへの対応（同じ綴りを毎回大文字化しない）
+    spellings: HashMap<String, Symbol>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.ids.get(name) {
+            return symbol;
+        }
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        self.ids.insert(name, symbol);
+        symbol
+    }
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with(|interner| interner.borrow_mut().intern(name))
+    }
+
+    // ワード名として大文字に正規化してインターンする
+    pub fn intern_word(spelling: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(&symbol) = interner.spellings.get(spelling) {
+                return symbol;
+            }
+            let symbol = interner.intern(&spelling.to_uppercase());
+            interner.spellings.insert(spelling.to_string(), symbol);
+            symbol
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().names[self.0 as usize])
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
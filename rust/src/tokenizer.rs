@@ -1,18 +1,26 @@
+use crate::types::Value;
+use crate::symbol::Symbol;
+use crate::trace::{self, LogLevel};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Number(i64, i64),  // 分子, 分母
     String(String),
     Boolean(bool),
-    Symbol(String),
+    Symbol(Symbol),
     VectorStart,
     VectorEnd,
     Nil,
     Description(String),
+    Literal(Value),  // 構築済みの値（LETで束縛された値など、トークナイザは生成しない）
 }
 
 pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
     let mut tokens = Vec::new();
     let mut chars = input.chars().peekable();
+    // `WORD[ ... ]` は `[ ... ] WORD` として扱う（閉じ括弧の深さとワード）
+    let mut depth = 0;
+    let mut postfix_words: Vec<(usize, Symbol)> = Vec::new();
     
     while let Some(&ch) = chars.peek() {
         // 空白をスキップ
@@ -74,6 +82,7 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
         // ベクトル開始/終了
         if ch == '[' {
             chars.next();
+            depth += 1;
             tokens.push(Token::VectorStart);
             continue;
         }
@@ -81,6 +90,11 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
         if ch == ']' {
             chars.next();
             tokens.push(Token::VectorEnd);
+            if postfix_words.last().is_some_and(|(d, _)| *d == depth) {
+                let (_, word) = postfix_words.pop().unwrap();
+                tokens.push(Token::Symbol(word));
+            }
+            depth = depth.saturating_sub(1);
             continue;
         }
         
@@ -98,8 +112,7 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             continue;
         }
         
-        // デバッグログ
-        web_sys::console::log_1(&format!("Tokenizing word: '{}'", word).into());
+        trace::log(LogLevel::Trace, "tokenizer", || format!("Tokenizing word: '{}'", word));
         
         // 数値の判定（整数と小数）
         if let Ok(num) = word.parse::<i64>() {
@@ -120,13 +133,13 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                 let denominator = 10_i64.pow(decimal_places);
                 let numerator = integer_part * denominator + decimal_part;
                 
-                web_sys::console::log_1(&format!("Parsed decimal {} as fraction {}/{}", word, numerator, denominator).into());
+                trace::log(LogLevel::Trace, "tokenizer", || format!("Parsed decimal {} as fraction {}/{}", word, numerator, denominator));
                 tokens.push(Token::Number(numerator, denominator));
             } else {
                 return Err(format!("Invalid number: {}", word));
             }
-        } else if word.contains('/') {
-            // 分数記法（例: 1/2）
+        } else if word.contains('/') && word.split('/').next().is_some_and(|numerator| numerator.parse::<i64>().is_ok()) {
+            // 分数記法（例: 1/2）、分子が数でなければシンボル（/や/CYCLEなど）
             let parts: Vec<&str> = word.split('/').collect();
             if parts.len() == 2 {
                 let numerator = parts[0].parse::<i64>()
@@ -150,7 +163,12 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                 "NIL" => tokens.push(Token::Nil),
                 _ => {
                     // シンボルは大文字に正規化
-                    tokens.push(Token::Symbol(word.to_uppercase()))
+                    if chars.peek() == Some(&'[') {
+                        // 直後の'['で始まるベクタの後ろに回す
+                        postfix_words.push((depth + 1, Symbol::intern_word(&word)));
+                    } else {
+                        tokens.push(Token::Symbol(Symbol::intern_word(&word)))
+                    }
                 },
             }
         }
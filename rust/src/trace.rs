@@ -0,0 +1,136 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// ログの重要度（後ろほど詳細）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name.to_uppercase().as_str() {
+            "ERROR" => Some(LogLevel::Error),
+            "WARN" => Some(LogLevel::Warn),
+            "INFO" => Some(LogLevel::Info),
+            "DEBUG" => Some(LogLevel::Debug),
+            "TRACE" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub target: &'static str,
+    pub message: String,
+}
+
+// ログの出力先（ホストが設定する）
+pub trait LogSink {
+    fn log(&mut self, record: &LogRecord);
+}
+
+// 標準エラー出力に書き出す
+pub struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn log(&mut self, record: &LogRecord) {
+        eprintln!("[{}] {}: {}", record.level, record.target, record.message);
+    }
+}
+
+// メモリに溜めておく（複製したハンドルから後で取り出す）
+#[derive(Clone, Default)]
+pub struct BufferSink {
+    records: Rc<RefCell<Vec<LogRecord>>>,
+}
+
+impl BufferSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn take(&self) -> Vec<LogRecord> {
+        std::mem::take(&mut *self.records.borrow_mut())
+    }
+}
+
+impl LogSink for BufferSink {
+    fn log(&mut self, record: &LogRecord) {
+        self.records.borrow_mut().push(record.clone());
+    }
+}
+
+// 出力先が設定されていなければログは無効（既定）
+#[derive(Default)]
+struct Logger {
+    level: Option<LogLevel>,
+    sink: Option<Box<dyn LogSink>>,
+}
+
+thread_local! {
+    static LOGGER: RefCell<Logger> = RefCell::new(Logger::default());
+}
+
+pub fn set_sink(sink: Box<dyn LogSink>, level: LogLevel) {
+    LOGGER.with(|logger| {
+        let mut logger = logger.borrow_mut();
+        logger.sink = Some(sink);
+        logger.level = Some(level);
+    });
+}
+
+// 出力先はそのままで、出力するレベルだけを変える
+pub fn set_level(level: LogLevel) {
+    LOGGER.with(|logger| logger.borrow_mut().level = Some(level));
+}
+
+pub fn clear_sink() {
+    LOGGER.with(|logger| *logger.borrow_mut() = Logger::default());
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    LOGGER.with(|logger| {
+        let logger = logger.borrow();
+        logger.sink.is_some() && logger.level.is_some_and(|max| level <= max)
+    })
+}
+
+// メッセージは出力する場合だけ組み立てる
+pub fn log(level: LogLevel, target: &'static str, message: impl FnOnce() -> String) {
+    if !enabled(level) {
+        return;
+    }
+    let record = LogRecord { level, target, message: message() };
+    LOGGER.with(|logger| {
+        // 出力先の中からログを出した場合（再入）は捨てる
+        if let Ok(mut logger) = logger.try_borrow_mut() {
+            if let Some(sink) = logger.sink.as_mut() {
+                sink.log(&record);
+            }
+        }
+    });
+}
@@ -1,4 +1,8 @@
 use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::{Rc, Weak};
+use crate::symbol::Symbol;
+use crate::stats;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Value {
@@ -10,9 +14,122 @@ pub enum ValueType {
     Number(Fraction),
     String(String),
     Boolean(bool),
-    Symbol(String),
-    Vector(Vec<Value>),
+    Symbol(Symbol),
+    Vector(VectorData),  // 複製しても要素は共有し、書き換えるときだけコピーする
     Nil,
+    Error(Box<Value>),  // THROWされた値、または組み込みワードのエラーメッセージ
+    Sequence(Box<Sequence>),  // 要素を必要になるまで計算しない遅延シーケンス
+}
+
+impl ValueType {
+    // エラーメッセージ用の型名
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ValueType::Number(_) => "number",
+            ValueType::String(_) => "string",
+            ValueType::Boolean(_) => "boolean",
+            ValueType::Symbol(_) => "symbol",
+            ValueType::Vector(_) => "vector",
+            ValueType::Nil => "nil",
+            ValueType::Error(_) => "error",
+            ValueType::Sequence(_) => "sequence",
+        }
+    }
+}
+
+// Vectorの要素（参照カウントで共有するコピーオンライト）
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VectorData(Rc<Vec<Value>>);
+
+impl VectorData {
+    // 要素を取り出す（ほかと共有していなければ複製しない）
+    pub fn into_vec(self) -> Vec<Value> {
+        Rc::try_unwrap(self.0).unwrap_or_else(|shared| {
+            stats::count_vector_elements(shared.len());
+            (*shared).clone()
+        })
+    }
+
+    // 要素の領域への弱参照（コンパイル結果のキャッシュ用、書き換えると別の領域になる）
+    pub(crate) fn downgrade(&self) -> Weak<Vec<Value>> {
+        Rc::downgrade(&self.0)
+    }
+}
+
+impl Deref for VectorData {
+    type Target = Vec<Value>;
+
+    fn deref(&self) -> &Vec<Value> {
+        &self.0
+    }
+}
+
+// 書き換えの直前に、共有されていれば複製する
+impl DerefMut for VectorData {
+    fn deref_mut(&mut self) -> &mut Vec<Value> {
+        if Rc::strong_count(&self.0) > 1 {
+            stats::count_vector_elements(self.0.len());
+        }
+        Rc::make_mut(&mut self.0)
+    }
+}
+
+impl From<Vec<Value>> for VectorData {
+    fn from(items: Vec<Value>) -> Self {
+        stats::count_vector_elements(items.len());
+        VectorData(Rc::new(items))
+    }
+}
+
+impl FromIterator<Value> for VectorData {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        VectorData::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl IntoIterator for VectorData {
+    type Item = Value;
+    type IntoIter = std::vec::IntoIter<Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a VectorData {
+    type Item = &'a Value;
+    type IntoIter = std::slice::Iter<'a, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    pub source: SequenceSource,
+    pub limit: Option<usize>,         // TAKEで指定した要素数の上限
+    pub conditions: Vec<VectorData>,  // TAKE-WHILEの述語（すべて真の間だけ続ける）
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SequenceSource {
+    Range { start: Fraction, end: Option<Fraction> },  // endは含まない（Noneなら無限）
+    Iterate { seed: Value, step: VectorData },         // seed, f(seed), f(f(seed)), ...
+    Items(VectorData),                                 // ベクトルの要素
+}
+
+impl Sequence {
+    pub fn new(source: SequenceSource) -> Self {
+        Sequence { source, limit: None, conditions: Vec::new() }
+    }
+
+    // 要素の数が有限と分かっているか
+    // （TAKE-WHILEだけでは分からないので、暗黙には計算せずFORCEを使う）
+    pub fn is_finite(&self) -> bool {
+        self.limit.is_some()
+            || matches!(self.source, SequenceSource::Range { end: Some(_), .. } | SequenceSource::Items(_))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -64,13 +181,14 @@ impl Fraction {
         Fraction::new(num, den)
     }
     
-    pub fn div(&self, other: &Fraction) -> Fraction {
+    // 0で割るとエラー（ユーザーの入力で起こるのでpanicしない）
+    pub fn div(&self, other: &Fraction) -> Result<Fraction, String> {
         if other.numerator == 0 {
-            panic!("Division by zero");
+            return Err("Division by zero".to_string());
         }
         let num = self.numerator * other.denominator;
         let den = self.denominator * other.numerator;
-        Fraction::new(num, den)
+        Ok(Fraction::new(num, den))
     }
     
     pub fn gt(&self, other: &Fraction) -> bool {
@@ -116,7 +234,30 @@ impl fmt::Display for Value {
                 write!(f, " ]")
             },
             ValueType::Nil => write!(f, "nil"),
+            ValueType::Error(payload) => write!(f, "error({})", payload),
+            ValueType::Sequence(seq) => write!(f, "{}", seq),
+        }
+    }
+}
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = |n: &Fraction| Value { val_type: ValueType::Number(n.clone()) };
+        match &self.source {
+            SequenceSource::Range { start, end: Some(end) } => write!(f, "range({} {})", number(start), number(end))?,
+            SequenceSource::Range { start, end: None } => write!(f, "range({} nil)", number(start))?,
+            SequenceSource::Iterate { seed, step } => {
+                write!(f, "iterate({} {})", seed, Value { val_type: ValueType::Vector(step.clone()) })?
+            },
+            SequenceSource::Items(items) => write!(f, "{}", Value { val_type: ValueType::Vector(items.clone()) })?,
+        }
+        for condition in &self.conditions {
+            write!(f, " take-while({})", Value { val_type: ValueType::Vector(condition.clone()) })?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " take({})", limit)?;
         }
+        Ok(())
     }
 }
 